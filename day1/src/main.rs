@@ -14,9 +14,9 @@ fn main() {
   The Fuel Counter-Upper needs to know the total fuel requirement. To find it, individually calculate the fuel needed for the mass of each module (your puzzle input), then add together all the fuel values.
   */
   let input = include_str!("input.txt");
-  let masses = input.split("\n").into_iter().filter_map(|s| s.parse().ok());
+  let masses = input.split("\n").filter_map(|s| s.parse().ok());
 
-  let ans1: i32 = masses.clone().map(|x| fuel_for_mass(x)).sum();
+  let ans1: i32 = masses.clone().map(fuel_for_mass).sum();
   println!("Part 1: {}", ans1); // 3184233
 
   /*
@@ -41,7 +41,7 @@ fn find_station(asteroids: &Vec<Coords>) -> (Coords, usize) {
         .iter()
         .map(|station| {
             (
-                station.clone(),
+                *station,
                 get_visible_asteroids(station, asteroids).len(),
             )
         })
@@ -63,8 +63,7 @@ fn get_vaporization_targets(station: &Coords, asteroids: &Vec<Coords>) -> Vec<Co
     // 3. create a new map with those asteroids removed
     let updated: Vec<_> = asteroids
         .iter()
-        .filter(|a| !targets.contains(a))
-        .map(|a| a.clone())
+        .filter(|a| !targets.contains(a)).copied()
         .collect();
 
     // 4. if there are any left besides the station, collect targets from the new map
@@ -72,7 +71,7 @@ fn get_vaporization_targets(station: &Coords, asteroids: &Vec<Coords>) -> Vec<Co
         targets.extend(get_vaporization_targets(station, &updated));
     }
 
-    return targets;
+    targets
 }
 
 fn get_visible_asteroids(station: &Coords, asteroids: &Vec<Coords>) -> Vec<Coords> {
@@ -122,8 +121,8 @@ impl Coords {
     }
     // https://stackoverflow.com/questions/328107/how-can-you-determine-a-point-is-between-two-other-points-on-a-line-segment
     pub fn is_between(&self, a: &Coords, b: &Coords) -> bool {
-        let seg = b.clone() - a.clone();
-        let to_a = self.clone() - a.clone();
+        let seg = *b - *a;
+        let to_a = *self - *a;
         let cross = seg.cross_len(to_a);
         if cross != 0 {
             return false;
@@ -133,7 +132,7 @@ impl Coords {
         if dot < 0 {
             return false;
         }
-        return dot <= seg.len_sq();
+        dot <= seg.len_sq()
     }
 }
 impl std::ops::Sub<Coords> for Coords {
@@ -1,4 +1,4 @@
-use intcode::{Program, Word};
+use intcode::{IntcodeError, Program, Word};
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive;
 use std::collections::HashMap;
@@ -93,7 +93,7 @@ impl Robot {
         out
     }
 
-    pub fn run(&mut self, prog: &Program) -> Result<(), String> {
+    pub fn run(&mut self, prog: &Program) -> Result<(), IntcodeError> {
         /*
         The Intcode program will serve as the brain of the robot. The program uses input instructions to access the robot's camera: provide 0 if the robot is over a black panel or 1 if the robot is over a white panel. Then, the program will output two values:
 
@@ -150,7 +150,7 @@ impl Moon {
     }
     fn apply_velocity(&mut self) {
         // simply add the velocity of each moon to its own position.
-        self.position += self.velocity.clone()
+        self.position += self.velocity
     }
     fn total_energy(&self) -> i32 {
         // The total energy for a single moon is its potential energy multiplied by its kinetic energy.
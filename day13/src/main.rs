@@ -63,8 +63,6 @@ impl Game {
         screen.clear();
         // screen.hide();
 
-        let mut input = 0;
-
         let mut state = rt.resume(None).unwrap();
         while let RuntimeState::Resumable(_) = state {
             // 1. execute draw commands
@@ -91,7 +89,7 @@ impl Game {
             let budget = Duration::from_millis(200);
             let start = Instant::now();
 
-            input = match screen.key_pressed(budget) {
+            let input = match screen.key_pressed(budget) {
                 Some(KeyEvent {
                     code: KeyCode::Left,
                     ..
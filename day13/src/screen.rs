@@ -1,3 +1,6 @@
+// crossterm 0.14's macros call the deprecated Error::description
+#![allow(deprecated)]
+
 use crossterm::{
     cursor,
     event::{poll, read, Event, KeyEvent},
@@ -18,6 +21,7 @@ impl Screen {
         self.moveto(0, 0);
         execute!(self.stdout, terminal::Clear(terminal::ClearType::All)).unwrap();
     }
+    #[allow(dead_code)]
     pub fn hide(&mut self) {
         execute!(self.stdout, cursor::Hide, cursor::DisableBlinking).unwrap();
     }
@@ -33,7 +37,7 @@ impl Screen {
         self.flush();
     }
     pub fn write(&mut self, s: &str) {
-        self.stdout.write(s.as_bytes()).unwrap();
+        self.stdout.write_all(s.as_bytes()).unwrap();
     }
     pub fn key_pressed(&mut self, timeout: std::time::Duration) -> Option<KeyEvent> {
         if poll(timeout).unwrap() {
@@ -36,10 +36,13 @@ fn main() {
   }
 }
 
+// more than enough for any pair that halts; the rest are skipped
+const FUEL: u64 = 10_000;
+
 fn find(prog: &Program, target: Word) -> Result<(Word, Word), String> {
   for noun in 0..=99 {
     for verb in 0..=99 {
-      if let Ok(res) = prog.run(noun, verb) {
+      if let Ok(res) = prog.run_limited(noun, verb, FUEL) {
         if res == target {
           return Ok((noun, verb));
         }
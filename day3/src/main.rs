@@ -35,7 +35,7 @@ fn find_intersections(w1: &Wire, w2: &Wire) -> Vec<Node> {
   out
 }
 
-fn closest_to_origin(nodes: &Vec<Node>) -> &Node {
+fn closest_to_origin(nodes: &[Node]) -> &Node {
   let mut iter = nodes.iter();
   let mut out = iter.next().unwrap();
   for n in iter {
@@ -43,25 +43,25 @@ fn closest_to_origin(nodes: &Vec<Node>) -> &Node {
       out = n
     }
   }
-  return out;
+  out
 }
 
-fn fastest_from_origin<'a, 'b>(
-  wires: &'a Vec<Wire>,
-  intersections: &'b Vec<Node>,
+fn fastest_from_origin<'b>(
+  wires: &[Wire],
+  intersections: &'b [Node],
 ) -> (&'b Node, Dist) {
   let mut iter = intersections.iter();
 
   let mut out = iter.next().unwrap();
-  let mut dist = wires.iter().filter_map(|w| w.locate(&out)).sum();
+  let mut dist = wires.iter().filter_map(|w| w.locate(out)).sum();
   for n in iter {
-    let d = wires.iter().filter_map(|w| w.locate(&n)).sum();
+    let d = wires.iter().filter_map(|w| w.locate(n)).sum();
     if d < dist {
       out = n;
       dist = d;
     }
   }
-  return (out, dist);
+  (out, dist)
 }
 
 type Coord = i32;
@@ -157,7 +157,7 @@ impl Wire {
       dist += seg.length();
     }
     // if we make it the whole way through without finding the node
-    return None;
+    None
   }
 }
 
@@ -225,10 +225,10 @@ impl Segment {
         if self.left_of(other) || self.right_of(other) || self.above(other) || self.below(other) {
           return None;
         }
-        return Some(Node {
+        Some(Node {
           x: self.left(),
           y: other.top(),
-        });
+        })
       }
     }
   }
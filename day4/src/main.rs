@@ -40,7 +40,7 @@ fn is_viable_pw(pw: &Password) -> bool {
     }
     prev = d;
   }
-  return has_double;
+  has_double
 }
 
 fn is_viable_pw2(pw: &Password) -> bool {
@@ -61,7 +61,7 @@ fn is_viable_pw2(pw: &Password) -> bool {
 
     prev = curr;
   }
-  return has_double;
+  has_double
 }
 
 fn digits(pw: &Password) -> Vec<u8> {
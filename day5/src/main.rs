@@ -1,4 +1,3 @@
-use intcode;
 use intcode::io::{IteratorInput, StdoutOutput};
 
 fn main() {
@@ -1,3 +1,4 @@
+use intcode::network::{Network, NodeId};
 use intcode::{Program, Word};
 use permute::permute;
 
@@ -30,67 +31,47 @@ fn main() {
   println!("Part 2: {}", max); // 17519904
 }
 
-fn run_chain(prog: &Program, phases: &Vec<Word>) -> Word {
-  let amps: Vec<_> = phases
-    .iter()
-    .map(|phase| {
-      let mut amp = prog.new_runtime();
-      amp.resume(None).unwrap();
-      amp.resume(Some(*phase)).unwrap();
-      amp
-    })
-    .collect();
+fn run_chain(prog: &Program, phases: &[Word]) -> Word {
+  let (mut net, amps) = amplifiers(prog, phases);
+  for pair in amps.windows(2) {
+    net.connect(pair[0], pair[1]);
+  }
+  net.run().unwrap();
+  net.outputs(amps[amps.len() - 1])[0]
+}
 
-  let mut input = 0;
-  for mut amp in amps {
-    // feed input, retrieve output, advance to next input
-    let (result, _done) = amp.step(input).unwrap();
-    input = result;
+fn run_feedback(prog: &Program, phases: &[Word]) -> Word {
+  let (mut net, amps) = amplifiers(prog, phases);
+  for (i, amp) in amps.iter().enumerate() {
+    net.connect(*amp, amps[(i + 1) % amps.len()]);
   }
-  input
+  net.run().unwrap();
+  *net.outputs(amps[amps.len() - 1]).last().unwrap()
 }
 
-fn run_feedback(prog: &Program, phases: &Vec<Word>) -> Word {
-  // run each amp to consume the phase setting, then wait for input
-  let mut amps: Vec<_> = phases
+// one amp per phase setting, each getting its phase first; the first amp
+// also gets the initial 0 signal
+fn amplifiers(prog: &Program, phases: &[Word]) -> (Network, Vec<NodeId>) {
+  let mut net = Network::new();
+  let amps = phases
     .iter()
     .enumerate()
     .map(|(i, phase)| {
-      let mut rt = prog.new_runtime();
-      // rt.trace = true;
-      rt.id = i.to_string();
-      rt.resume(None).unwrap();
-      rt.resume(Some(*phase)).unwrap();
-      // assert_eq!(rt.state(), RuntimeState::Resumable(None));
-      rt
+      let inputs = if i == 0 { vec![*phase, 0] } else { vec![*phase] };
+      net.add_node(&i.to_string(), prog, inputs)
     })
     .collect();
-
-  let mut input = 0;
-  loop {
-    let mut complete = 0;
-    for amp in &mut amps {
-      let (result, done) = amp.step(input).unwrap();
-      input = result;
-      if done {
-        complete += 1  
-      }
-    }
-    if complete == phases.len() {
-      break;
-    }
-  }
-  input
+  (net, amps)
 }
 
 fn find_max<F>(prog: &Program, circuit: F, phase_settings: Vec<Word>) -> (Word, Vec<Word>)
 where
-  F: Fn(&Program, &Vec<Word>) -> Word,
+  F: Fn(&Program, &[Word]) -> Word,
 {
   let mut max = 0;
   let mut max_phases = Vec::new();
   for phases in permute(phase_settings) {
-    let output = circuit(&prog, &phases);
+    let output = circuit(prog, &phases);
     if output > max {
       max = output;
       max_phases = phases.clone();
@@ -106,7 +87,7 @@ mod tests {
   #[test]
   fn sample1() {
     let prog = Program::parse("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0").unwrap();
-    assert_eq!(43210, run_chain(&prog, &vec![4, 3, 2, 1, 0]));
+    assert_eq!(43210, run_chain(&prog, &[4, 3, 2, 1, 0]));
     assert_eq!(
       (43210, vec![4, 3, 2, 1, 0]),
       find_max(&prog, run_chain, vec![0, 1, 2, 3, 4])
@@ -117,7 +98,7 @@ mod tests {
     let prog =
       Program::parse("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0")
         .unwrap();
-    assert_eq!(54321, run_chain(&prog, &vec![0, 1, 2, 3, 4]));
+    assert_eq!(54321, run_chain(&prog, &[0, 1, 2, 3, 4]));
     assert_eq!(
       (54321, vec![0, 1, 2, 3, 4]),
       find_max(&prog, run_chain, vec![0, 1, 2, 3, 4])
@@ -126,7 +107,7 @@ mod tests {
   #[test]
   fn sample3() {
     let prog = Program::parse("3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0").unwrap();
-    assert_eq!(65210, run_chain(&prog, &vec![1, 0, 4, 3, 2]));
+    assert_eq!(65210, run_chain(&prog, &[1, 0, 4, 3, 2]));
     assert_eq!(
       (65210, vec![1, 0, 4, 3, 2]),
       find_max(&prog, run_chain, vec![0, 1, 2, 3, 4])
@@ -138,7 +119,7 @@ mod tests {
       "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
     )
     .unwrap();
-    assert_eq!(139629729, run_feedback(&prog, &vec![9, 8, 7, 6, 5]));
+    assert_eq!(139629729, run_feedback(&prog, &[9, 8, 7, 6, 5]));
     assert_eq!(
       (139629729, vec![9, 8, 7, 6, 5]),
       find_max(&prog, run_feedback, vec![5, 6, 7, 8, 9])
@@ -147,7 +128,7 @@ mod tests {
   #[test]
   fn sample5() {
     let prog = Program::parse("3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10").unwrap();
-    assert_eq!(18216, run_feedback(&prog, &vec![9, 8, 7, 6, 5]));
+    assert_eq!(18216, run_feedback(&prog, &[9, 8, 7, 6, 5]));
     assert_eq!(
       (18216, vec![9, 8, 7, 6, 5]),
       find_max(&prog, run_feedback, vec![5, 6, 7, 8, 9])
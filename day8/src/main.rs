@@ -56,7 +56,7 @@ fn main() {
         };
       }
     }
-    print!("\n");
+    println!();
   }
   /*
   #    ####   ## #  #  ##
@@ -0,0 +1,29 @@
+// Times the day9 BOOST program in test mode (1) and sensor boost mode (2).
+// Run with `cargo bench -p intcode`.
+use intcode::Program;
+use std::time::{Duration, Instant};
+
+fn bench<F: FnMut()>(name: &str, iters: u32, mut f: F) {
+    // warm up
+    f();
+    let mut best = Duration::from_secs(u64::MAX);
+    let start = Instant::now();
+    for _ in 0..iters {
+        let t = Instant::now();
+        f();
+        best = best.min(t.elapsed());
+    }
+    let mean = start.elapsed() / iters;
+    println!("{:<16} mean {:>10.3?}  best {:>10.3?}  ({} iters)", name, mean, best, iters);
+}
+
+fn main() {
+    let prog = Program::parse(include_str!("../../day9/src/input.txt")).unwrap();
+
+    bench("day9 test mode", 1000, || {
+        assert_eq!(1, prog.run_collect_output(vec![1]).unwrap().len());
+    });
+    bench("day9 boost mode", 50, || {
+        assert_eq!(1, prog.run_collect_output(vec![2]).unwrap().len());
+    });
+}
@@ -0,0 +1,384 @@
+use super::ops::{Operation, Operations};
+use super::{IntcodeError, Program, Word};
+use std::collections::HashMap;
+
+// Assembly syntax, one statement per line:
+//
+//   ; comments run to the end of the line
+//   start:  input [n]             ; labels end with ':'
+//           mul [n], #3, rb-2     ; [pos], #imm, rb+n
+//           jz [n], #start
+//           halt
+//   n:      .data 0, start        ; raw words, numbers or labels
+//
+// Mnemonics are the operation names from ops.rs, or one of the aliases below.
+
+const ALIASES: &[(&str, &str)] = &[
+    ("mul", "multiply"),
+    ("in", "input"),
+    ("out", "output"),
+    ("jt", "jump-if-true"),
+    ("jnz", "jump-if-true"),
+    ("jf", "jump-if-false"),
+    ("jz", "jump-if-false"),
+    ("lt", "less-than"),
+    ("eq", "equals"),
+    ("rbo", "relative-base-offset"),
+    ("arb", "relative-base-offset"),
+    ("hlt", "halt"),
+];
+
+pub struct Assembly {
+    pub program: Program,
+    pub symbols: HashMap<String, Word>,
+}
+
+pub fn assemble(src: &str) -> Result<Assembly, IntcodeError> {
+    assemble_with(src, &Operations::new())
+}
+
+// like assemble, with mnemonics from ops, e.g. ones added with register
+pub fn assemble_with(src: &str, ops: &Operations) -> Result<Assembly, IntcodeError> {
+    let mut symbols = HashMap::new();
+    let mut words = Vec::new();
+
+    // pass 1: lay out words and collect labels
+    for (i, text) in src.lines().enumerate() {
+        let mut cur = Cursor::new(i + 1, text);
+        let (label, stmt) = cur.line(ops)?;
+        if let Some((name, col)) = label {
+            if symbols.contains_key(&name) {
+                return Err(cur.err_at(col, format!("duplicate label '{}'", name)));
+            }
+            symbols.insert(name, words.len() as Word);
+        }
+        words.extend(stmt);
+    }
+
+    // pass 2: resolve label references
+    let operations = words
+        .into_iter()
+        .map(|v| match v {
+            Value::Num(w) => Ok(w),
+            Value::Label(name, line, column) => {
+                symbols
+                    .get(&name)
+                    .copied()
+                    .ok_or_else(|| IntcodeError::Assemble {
+                        line,
+                        column,
+                        message: format!("undefined label '{}'", name),
+                    })
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Assembly {
+        program: Program::new(operations),
+        symbols,
+    })
+}
+
+enum Value {
+    Num(Word),
+    // name, line, column
+    Label(String, usize, usize),
+}
+
+enum Operand {
+    Position(Value),
+    Immediate(Value),
+    Relative(Word),
+}
+
+// a line's label (with its column) and the words it assembles to
+type ParsedLine = (Option<(String, usize)>, Vec<Value>);
+
+struct Cursor {
+    line: usize,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Cursor {
+    fn new(line: usize, text: &str) -> Self {
+        Cursor {
+            line,
+            chars: text.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn line(&mut self, ops: &Operations) -> Result<ParsedLine, IntcodeError> {
+        self.skip_ws();
+        let mut label = None;
+        if self.at_end() {
+            return Ok((None, vec![]));
+        }
+
+        let col = self.pos;
+        let directive = self.eat('.');
+        let mut name = self
+            .ident()
+            .ok_or_else(|| self.err("expected a label, mnemonic or directive"))?;
+        self.skip_ws();
+        if !directive && self.eat(':') {
+            label = Some((name, col));
+            self.skip_ws();
+            if self.at_end() {
+                return Ok((label, vec![]));
+            }
+            let col = self.pos;
+            if self.eat('.') {
+                name = self
+                    .ident()
+                    .ok_or_else(|| self.err("expected a directive"))?;
+                let words = self.directive(&name, col)?;
+                return Ok((label, words));
+            }
+            name = self
+                .ident()
+                .ok_or_else(|| self.err("expected a mnemonic"))?;
+            let words = self.instruction(ops, &name, col)?;
+            return Ok((label, words));
+        }
+
+        let words = if directive {
+            self.directive(&name, col)?
+        } else {
+            self.instruction(ops, &name, col)?
+        };
+        Ok((label, words))
+    }
+
+    fn directive(&mut self, name: &str, col: usize) -> Result<Vec<Value>, IntcodeError> {
+        if name != "data" {
+            return Err(self.err_at(col, format!("unknown directive '.{}'", name)));
+        }
+        let mut words = Vec::new();
+        loop {
+            self.skip_ws();
+            words.push(self.value()?);
+            self.skip_ws();
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.end()?;
+        Ok(words)
+    }
+
+    fn instruction(
+        &mut self,
+        ops: &Operations,
+        name: &str,
+        col: usize,
+    ) -> Result<Vec<Value>, IntcodeError> {
+        let op = lookup(ops, name)
+            .ok_or_else(|| self.err_at(col, format!("unknown mnemonic '{}'", name)))?;
+
+        let mut operands = Vec::new();
+        self.skip_ws();
+        if !self.at_end() {
+            loop {
+                self.skip_ws();
+                operands.push((self.pos, self.operand()?));
+                self.skip_ws();
+                if !self.eat(',') {
+                    break;
+                }
+            }
+        }
+        self.end()?;
+        if operands.len() != op.params as usize {
+            return Err(self.err_at(
+                col,
+                format!(
+                    "{} takes {} params, got {}",
+                    op.name,
+                    op.params,
+                    operands.len()
+                ),
+            ));
+        }
+
+        if let Some(i) = written_param(&op) {
+            if let (pos, Operand::Immediate(_)) = &operands[i] {
+                let message = format!(
+                    "{} writes to param {}, so it can't be immediate",
+                    op.name,
+                    i + 1
+                );
+                return Err(self.err_at(*pos, message));
+            }
+        }
+
+        let mut word = op.opcode as Word;
+        let mut scale = 100;
+        let mut words = vec![];
+        for (_, operand) in operands {
+            let (mode, value) = match operand {
+                Operand::Position(v) => (0, v),
+                Operand::Immediate(v) => (1, v),
+                Operand::Relative(n) => (2, Value::Num(n)),
+            };
+            word += mode * scale;
+            scale *= 10;
+            words.push(value);
+        }
+        words.insert(0, Value::Num(word));
+        Ok(words)
+    }
+
+    fn operand(&mut self) -> Result<Operand, IntcodeError> {
+        if self.eat('#') {
+            return Ok(Operand::Immediate(self.value()?));
+        }
+        if self.eat('[') {
+            self.skip_ws();
+            let v = self.value()?;
+            self.skip_ws();
+            if !self.eat(']') {
+                return Err(self.err("expected ']'"));
+            }
+            return Ok(Operand::Position(v));
+        }
+        if self.rest_starts_with("rb") {
+            let after = self.chars.get(self.pos + 2).copied();
+            match after {
+                Some('+') | Some('-') => {
+                    self.pos += 2;
+                    return Ok(Operand::Relative(self.number()?));
+                }
+                None | Some(',') | Some(';') => {
+                    self.pos += 2;
+                    return Ok(Operand::Relative(0));
+                }
+                Some(c) if c.is_whitespace() => {
+                    self.pos += 2;
+                    return Ok(Operand::Relative(0));
+                }
+                _ => (),
+            }
+        }
+        Err(self.err("expected #imm, [pos] or rb+n"))
+    }
+
+    fn value(&mut self) -> Result<Value, IntcodeError> {
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' => Ok(Value::Num(self.number()?)),
+            _ => {
+                let col = self.pos;
+                let name = self
+                    .ident()
+                    .ok_or_else(|| self.err("expected a number or label"))?;
+                Ok(Value::Label(name, self.line, col + 1))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Word, IntcodeError> {
+        let start = self.pos;
+        if let Some('+') | Some('-') = self.peek() {
+            self.pos += 1;
+        }
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            self.pos += 1;
+        }
+        let s: String = self.chars[start..self.pos].iter().collect();
+        s.parse()
+            .map_err(|_| self.err_at(start, format!("invalid number '{}'", s)))
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(c) if c.is_alphabetic() || c == '_' => (),
+            _ => return None,
+        }
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_alphanumeric() || c == '_' || c == '-') {
+                break;
+            }
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn end(&mut self) -> Result<(), IntcodeError> {
+        self.skip_ws();
+        if self.at_end() {
+            Ok(())
+        } else {
+            Err(self.err(format!("unexpected '{}'", self.chars[self.pos])))
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        matches!(self.peek(), None | Some(';'))
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn rest_starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn err(&self, message: impl Into<String>) -> IntcodeError {
+        self.err_at(self.pos, message)
+    }
+
+    // pos is a 0-based char index, columns are reported 1-based
+    fn err_at(&self, pos: usize, message: impl Into<String>) -> IntcodeError {
+        IntcodeError::Assemble {
+            line: self.line,
+            column: pos + 1,
+            message: message.into(),
+        }
+    }
+}
+
+// the param a built-in operation writes its result to
+fn written_param(op: &Operation) -> Option<usize> {
+    if *op == Operation::INP {
+        Some(0)
+    } else if [Operation::ADD, Operation::MUL, Operation::LT, Operation::EQ].contains(op) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn lookup(ops: &Operations, name: &str) -> Option<Operation> {
+    let name = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map(|(_, full)| *full)
+        .unwrap_or(name);
+    ops.by_name(name)
+}
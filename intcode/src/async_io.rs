@@ -0,0 +1,82 @@
+use super::{Int, IntcodeError, Program, Runtime, RuntimeState};
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::fmt::Display;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::Poll;
+
+// Async counterpart to run_io: inputs come from a Stream and outputs go to a
+// Sink, so many machines can share an executor. Every input request yields
+// to the executor once, even if a value is already waiting, so a machine
+// that's never starved of input can't hog the thread.
+//
+// The sink is flushed after each output but never closed; pass `&mut sink`
+// to keep using it afterwards.
+impl<W: Int> Runtime<W> {
+    pub async fn run_async<I, O>(
+        &mut self,
+        mut input: I,
+        mut output: O,
+    ) -> Result<(), IntcodeError<W>>
+    where
+        I: Stream<Item = W> + Unpin,
+        O: Sink<W> + Unpin,
+        O::Error: Display,
+    {
+        let mut val = None;
+        loop {
+            match self.resume(val.take())? {
+                RuntimeState::Resumable(Some(x)) => send(&mut output, x).await?,
+                RuntimeState::Resumable(None) => {
+                    yield_now().await;
+                    let next = poll_fn(|cx| Pin::new(&mut input).poll_next(cx)).await;
+                    val = Some(next.ok_or(IntcodeError::InputExhausted)?);
+                }
+                RuntimeState::Complete => return Ok(()),
+                RuntimeState::Break(bp) => {
+                    return Err(IntcodeError::UnexpectedBreak { breakpoint: bp })
+                }
+                RuntimeState::OutOfFuel => return Err(self.out_of_fuel()),
+            }
+        }
+    }
+}
+
+impl<W: Int> Program<W> {
+    pub async fn run_async<I, O>(&self, input: I, output: O) -> Result<(), IntcodeError<W>>
+    where
+        I: Stream<Item = W> + Unpin,
+        O: Sink<W> + Unpin,
+        O::Error: Display,
+    {
+        self.new_runtime().run_async(input, output).await
+    }
+}
+
+async fn send<W, O>(output: &mut O, val: W) -> Result<(), IntcodeError<W>>
+where
+    O: Sink<W> + Unpin,
+    O::Error: Display,
+{
+    let err = |e: O::Error| IntcodeError::Other(format!("output sink: {}", e));
+    poll_fn(|cx| Pin::new(&mut *output).poll_ready(cx))
+        .await
+        .map_err(err)?;
+    Pin::new(&mut *output).start_send(val).map_err(err)?;
+    poll_fn(|cx| Pin::new(&mut *output).poll_flush(cx))
+        .await
+        .map_err(err)
+}
+
+fn yield_now() -> impl Future<Output = ()> {
+    let mut yielded = false;
+    poll_fn(move |cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+}
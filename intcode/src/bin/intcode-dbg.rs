@@ -0,0 +1,267 @@
+// Interactive debugger for intcode programs.
+//
+//   cargo run -p intcode --bin intcode-dbg -- day9/src/input.txt
+//
+// Type `help` at the prompt for a list of commands.
+use intcode::{Breakpoint, IntcodeError, Program, Runtime, RuntimeState, Word};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+s, step [n]          execute n instructions (default 1)
+c, continue          run until a breakpoint, halt, or input with nothing queued
+b, break <addr>      break before executing the instruction at addr
+b r|w <addr>         break after addr is read (r) or written (w)
+b op <opcode>        break before executing any instruction with this opcode
+d, delete ...        remove a breakpoint, same syntax as break
+bl                   list breakpoints
+i, inst              show memory around pc and the current instruction
+l, list [addr] [n]   disassemble n instructions from addr (default pc, 10)
+x <addr> [n]         show n words of memory from addr (default 1)
+set <addr> <val>     write val to memory at addr
+rb [val]             show or set the relative base
+fuel [n]             show instructions executed, or stop after n more
+in <val>...          queue values for input instructions
+out                  show all outputs so far
+q, quit              exit
+";
+
+struct Debugger {
+    rt: Runtime,
+    inputs: VecDeque<Word>,
+    outputs: Vec<Word>,
+    // the program is blocked on an input instruction
+    waiting: bool,
+    done: bool,
+}
+
+impl Debugger {
+    fn new(prog: &Program) -> Self {
+        Debugger {
+            rt: prog.new_runtime(),
+            inputs: VecDeque::new(),
+            outputs: Vec::new(),
+            waiting: false,
+            done: false,
+        }
+    }
+
+    // run one instruction (step) or until something stops the program, and
+    // report why. Returns whether it's ok to keep going.
+    fn advance(&mut self, step: bool) -> Result<bool, IntcodeError> {
+        if self.done {
+            println!("program has halted");
+            return Ok(false);
+        }
+        let val = if self.waiting {
+            match self.inputs.pop_front() {
+                Some(v) => Some(v),
+                None => {
+                    println!("waiting for input, queue some with `in`");
+                    return Ok(false);
+                }
+            }
+        } else {
+            None
+        };
+        self.waiting = false;
+
+        let state = if step {
+            self.rt.step_instruction(val)?
+        } else {
+            self.rt.resume(val)?
+        };
+        match state {
+            RuntimeState::Resumable(Some(x)) => {
+                println!("output: {}", x);
+                self.outputs.push(x);
+                Ok(true)
+            }
+            RuntimeState::Resumable(None) => {
+                self.waiting = true;
+                Ok(true)
+            }
+            RuntimeState::Break(Breakpoint::Step) => Ok(true),
+            RuntimeState::Break(bp) => {
+                println!("break: {}", bp);
+                Ok(false)
+            }
+            RuntimeState::OutOfFuel => {
+                println!(
+                    "out of fuel after {} instructions",
+                    self.rt.instructions_executed()
+                );
+                Ok(false)
+            }
+            RuntimeState::Complete => {
+                println!("halted");
+                self.done = true;
+                Ok(false)
+            }
+        }
+    }
+
+    fn show_instruction(&self) {
+        let pc = self.rt.pc();
+        print!("{}", self.rt.format_state(pc - 4, pc + 12));
+        println!("pc={} rb={}", pc, self.rt.relative_base());
+    }
+
+    fn list(&self, mut addr: Word, n: usize) {
+        for _ in 0..n {
+            let marker = if addr == self.rt.pc() { "=>" } else { "  " };
+            match self
+                .rt
+                .operations()
+                .decode(addr, |i| self.rt.peek(addr + i))
+            {
+                Ok(inst) => {
+                    println!("{} {:04}: {}", marker, addr, inst);
+                    addr += inst.size() as Word;
+                }
+                Err(_) => {
+                    let word = self.rt.peek(addr).unwrap_or(0);
+                    println!("{} {:04}: .data {}", marker, addr, word);
+                    addr += 1;
+                }
+            }
+        }
+    }
+
+    // returns false to quit
+    fn command(&mut self, line: &str) -> Result<bool, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let args = words.get(1..).unwrap_or(&[]);
+        match words.first().copied().unwrap_or("") {
+            "" => (),
+            "s" | "step" => {
+                let n = arg_or(args, 0, 1)?;
+                for _ in 0..n {
+                    if !self.advance(true).map_err(|e| e.to_string())? {
+                        break;
+                    }
+                }
+                if !self.done {
+                    self.show_instruction();
+                }
+            }
+            "c" | "continue" => {
+                while self.advance(false).map_err(|e| e.to_string())? {}
+                if !self.done {
+                    self.show_instruction();
+                }
+            }
+            "b" | "break" => self.rt.add_breakpoint(parse_breakpoint(args)?),
+            "d" | "delete" => {
+                if !self.rt.remove_breakpoint(parse_breakpoint(args)?) {
+                    return Err("no such breakpoint".to_string());
+                }
+            }
+            "bl" => {
+                for bp in self.rt.breakpoints() {
+                    println!("{}", bp);
+                }
+            }
+            "i" | "inst" => self.show_instruction(),
+            "l" | "list" => {
+                let addr = arg_or(args, 0, self.rt.pc())?;
+                let n = arg_or(args, 1, 10)?;
+                self.list(addr, n);
+            }
+            "x" => {
+                let addr: Word = arg(args, 0)?;
+                let n: Word = arg_or(args, 1, 1)?;
+                let end = addr.checked_add(n).ok_or_else(|| {
+                    format!("{} words from {} is out of range", n, addr)
+                })?;
+                for a in addr..end {
+                    match self.rt.peek(a) {
+                        Ok(v) => println!("{:04}: {}", a, v),
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+            }
+            "set" => {
+                self.rt
+                    .set(arg(args, 0)?, arg(args, 1)?)
+                    .map_err(|e| e.to_string())?;
+            }
+            "rb" => match args.first() {
+                None => println!("rb={}", self.rt.relative_base()),
+                Some(_) => self.rt.set_relative_base(arg(args, 0)?),
+            },
+            "fuel" => match args.first() {
+                None => println!(
+                    "executed={} fuel={:?}",
+                    self.rt.instructions_executed(),
+                    self.rt.fuel()
+                ),
+                Some(_) => self.rt.set_fuel(Some(arg(args, 0)?)),
+            },
+            "in" => {
+                for i in 0..args.len() {
+                    self.inputs.push_back(arg(args, i)?);
+                }
+            }
+            "out" => println!("{:?}", self.outputs),
+            "h" | "help" => print!("{}", HELP),
+            "q" | "quit" => return Ok(false),
+            cmd => return Err(format!("unknown command '{}', try help", cmd)),
+        }
+        Ok(true)
+    }
+}
+
+fn arg<T: std::str::FromStr>(args: &[&str], i: usize) -> Result<T, String> {
+    let s = args.get(i).ok_or("missing argument")?;
+    s.parse().map_err(|_| format!("invalid argument '{}'", s))
+}
+
+fn arg_or<T: std::str::FromStr>(args: &[&str], i: usize, default: T) -> Result<T, String> {
+    match args.get(i) {
+        None => Ok(default),
+        Some(_) => arg(args, i),
+    }
+}
+
+fn parse_breakpoint(args: &[&str]) -> Result<Breakpoint, String> {
+    match args.first().copied() {
+        Some("r") => Ok(Breakpoint::Read(arg(args, 1)?)),
+        Some("w") => Ok(Breakpoint::Write(arg(args, 1)?)),
+        Some("op") => Ok(Breakpoint::Opcode(arg(args, 1)?)),
+        _ => Ok(Breakpoint::Pc(arg(args, 0)?)),
+    }
+}
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(p) => p,
+        None => {
+            eprintln!("usage: intcode-dbg <program.txt>");
+            std::process::exit(2);
+        }
+    };
+    let prog = Program::from_file(&path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    });
+
+    let mut dbg = Debugger::new(&prog);
+    dbg.show_instruction();
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().unwrap();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+        match dbg.command(&line) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
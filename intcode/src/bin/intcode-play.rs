@@ -0,0 +1,33 @@
+// Play an ASCII intcode program at the terminal, optionally logging the
+// whole session.
+//
+//   cargo run -p intcode --bin intcode-play -- <program.txt> [transcript.txt]
+use intcode::session::Session;
+use intcode::Program;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let path = match args.first() {
+        Some(p) => p,
+        None => {
+            eprintln!("usage: intcode-play <program.txt> [transcript.txt]");
+            std::process::exit(2);
+        }
+    };
+    let prog = Program::from_file(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    });
+
+    let mut session = Session::new(&prog);
+    if let Some(log) = args.get(1) {
+        session = session.transcript(log).unwrap_or_else(|e| {
+            eprintln!("{}: {}", log, e);
+            std::process::exit(1);
+        });
+    }
+    if let Err(e) = session.run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
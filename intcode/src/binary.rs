@@ -0,0 +1,181 @@
+use super::mem::Memory;
+use super::{IntcodeError, Program, Runtime, Word};
+use std::convert::TryFrom;
+
+// A compact binary form of a program or memory image:
+//
+//   "ICB" 1                    magic and format version
+//   n w0 w1 ... wn-1           words from address 0
+//   k                          number of sparse segments, then k of
+//     addr len w0 ... wlen-1   len words starting at addr
+//
+// Counts and addresses are unsigned LEB128 varints, and words are zigzag
+// encoded first so small negative numbers stay small too. Programs never
+// have sparse segments; memory images use them for very high addresses.
+// Only Word programs can be written this way.
+
+const MAGIC: &[u8] = b"ICB";
+const VERSION: u8 = 1;
+
+impl Program {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode(&self.operations, &[])
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, IntcodeError> {
+        let (words, _) = decode(bytes, false)?;
+        Ok(Program::new(words))
+    }
+}
+
+impl Runtime {
+    // All of memory, but none of the rest of the machine's state; see
+    // snapshot() for that.
+    pub fn memory_image(&self) -> Vec<u8> {
+        encode(self.mem.dense(), &self.mem.sparse())
+    }
+
+    // a new runtime starting at pc 0 with this memory
+    pub fn from_memory_image(bytes: &[u8]) -> Result<Runtime, IntcodeError> {
+        let (words, sparse) = decode(bytes, true)?;
+        let mut rt = Runtime::new(vec![]);
+        let mut mem = Memory::new(words);
+        for (addr, word) in sparse {
+            mem.set(addr, word);
+        }
+        rt.mem = mem;
+        Ok(rt)
+    }
+}
+
+fn encode(dense: &[Word], sparse: &[(usize, Word)]) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    put_words(&mut out, dense);
+
+    // runs of consecutive addresses
+    let mut segments: Vec<(usize, Vec<Word>)> = Vec::new();
+    for &(addr, word) in sparse {
+        match segments.last_mut() {
+            Some((start, words)) if *start + words.len() == addr => words.push(word),
+            _ => segments.push((addr, vec![word])),
+        }
+    }
+    put_varint(&mut out, segments.len() as u64);
+    for (addr, words) in &segments {
+        put_varint(&mut out, *addr as u64);
+        put_words(&mut out, words);
+    }
+    out
+}
+
+type Image = (Vec<Word>, Vec<(usize, Word)>);
+
+fn decode(bytes: &[u8], allow_sparse: bool) -> Result<Image, IntcodeError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(err(0, "not an intcode binary"));
+    }
+    match bytes.get(MAGIC.len()) {
+        Some(&VERSION) => (),
+        Some(v) => return Err(err(MAGIC.len(), format!("unsupported version {}", v))),
+        None => return Err(err(MAGIC.len(), "missing version")),
+    }
+    let mut r = Reader {
+        bytes,
+        pos: MAGIC.len() + 1,
+    };
+
+    let dense = r.words()?;
+    let mut sparse = Vec::new();
+    let at = r.pos;
+    let segments = r.varint()?;
+    if segments > 0 && !allow_sparse {
+        return Err(err(at, "a program can't have sparse segments"));
+    }
+    for _ in 0..segments {
+        let at = r.pos;
+        let addr = r.usize()?;
+        let words = r.words()?;
+        if addr.checked_add(words.len()).is_none() {
+            return Err(err(at, format!("segment at {} is out of range", addr)));
+        }
+        sparse.extend(words.into_iter().enumerate().map(|(i, w)| (addr + i, w)));
+    }
+    if r.pos != bytes.len() {
+        return Err(err(r.pos, "unexpected data after the last segment"));
+    }
+    Ok((dense, sparse))
+}
+
+fn put_words(out: &mut Vec<u8>, words: &[Word]) {
+    put_varint(out, words.len() as u64);
+    for &w in words {
+        put_varint(out, ((w << 1) ^ (w >> 63)) as u64);
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn varint(&mut self) -> Result<u64, IntcodeError> {
+        let start = self.pos;
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = *self
+                .bytes
+                .get(self.pos)
+                .ok_or_else(|| err(self.pos, "unexpected end of data"))?;
+            self.pos += 1;
+            // the tenth byte only has room for one more bit
+            if shift == 63 && b > 1 {
+                break;
+            }
+            n |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(err(start, "varint too long"))
+    }
+
+    fn usize(&mut self) -> Result<usize, IntcodeError> {
+        let at = self.pos;
+        let n = self.varint()?;
+        usize::try_from(n).map_err(|_| err(at, format!("{} is too big", n)))
+    }
+
+    fn words(&mut self) -> Result<Vec<Word>, IntcodeError> {
+        let at = self.pos;
+        let n = self.usize()?;
+        // every word takes at least a byte, so don't trust a count that
+        // couldn't fit in what's left
+        let left = self.bytes.len() - self.pos;
+        if n > left {
+            return Err(err(at, format!("{} words but only {} bytes left", n, left)));
+        }
+        let mut words = Vec::with_capacity(n);
+        for _ in 0..n {
+            let z = self.varint()?;
+            words.push((z >> 1) as Word ^ -((z & 1) as Word));
+        }
+        Ok(words)
+    }
+}
+
+fn err(offset: usize, message: impl Into<String>) -> IntcodeError {
+    IntcodeError::Binary {
+        offset,
+        message: message.into(),
+    }
+}
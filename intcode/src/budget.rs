@@ -0,0 +1,81 @@
+use super::{Int, IntcodeError, Runtime};
+use std::time::Instant;
+
+// how many instructions run between looks at the clock
+const DEADLINE_CHECK_INTERVAL: u32 = 1024;
+
+// Limits on how long resume() may run before stopping with
+// RuntimeState::OutOfFuel. Neither is set by default.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Budget {
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    // instructions until the deadline is next checked; 0 checks straight away
+    until_check: u32,
+}
+
+impl<W: Int> Runtime<W> {
+    // Stop after this many more instructions, or None for no limit. Once it
+    // runs out, resume() returns OutOfFuel until more is added.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.budget.fuel = fuel;
+    }
+    // top up the remaining fuel; does nothing if there's no limit
+    pub fn add_fuel(&mut self, fuel: u64) {
+        if let Some(f) = &mut self.budget.fuel {
+            *f = f.saturating_add(fuel);
+        }
+    }
+    pub fn fuel(&self) -> Option<u64> {
+        self.budget.fuel
+    }
+    // Stop once this time has passed, or None for no deadline. The clock is
+    // only checked every so often, so a few more instructions may run; move
+    // the deadline (or clear it) to continue.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.budget.deadline = deadline;
+        self.budget.until_check = 0;
+    }
+    pub fn deadline(&self) -> Option<Instant> {
+        self.budget.deadline
+    }
+    // instructions completed since the runtime was created
+    pub fn instructions_executed(&self) -> u64 {
+        self.executed
+    }
+
+    pub(crate) fn budget_limited(&self) -> bool {
+        self.budget.fuel.is_some() || self.budget.deadline.is_some()
+    }
+
+    // called before each instruction
+    pub(crate) fn budget_exhausted(&mut self) -> bool {
+        let b = &mut self.budget;
+        if b.fuel == Some(0) {
+            return true;
+        }
+        if let Some(deadline) = b.deadline {
+            if b.until_check == 0 {
+                if Instant::now() >= deadline {
+                    return true;
+                }
+                b.until_check = DEADLINE_CHECK_INTERVAL;
+            }
+            b.until_check -= 1;
+        }
+        false
+    }
+    // called after each instruction completes
+    pub(crate) fn budget_spend(&mut self) {
+        self.executed += 1;
+        if let Some(f) = &mut self.budget.fuel {
+            *f = f.saturating_sub(1);
+        }
+    }
+    // for helpers that don't expect to stop for fuel
+    pub(crate) fn out_of_fuel(&self) -> IntcodeError<W> {
+        IntcodeError::OutOfFuel {
+            executed: self.executed,
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use super::ops::{Arithmetic, Instruction, Operation, Operations, Param};
+use super::{Int, Program, Word};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+
+// Control flow graph recovered by following jumps from address 0.
+//
+// This only sees the program as loaded: jumps through memory (non-immediate
+// targets) are marked Indirect and not followed, and code the program writes
+// for itself at runtime isn't known about. Subroutine calls are recognised by
+// the usual convention of storing a constant return address at rb+0 and then
+// jumping, so code after a call is still found even though the matching
+// return (e.g. `jz #0, rb`) is indirect.
+#[derive(Debug, PartialEq)]
+pub struct Cfg<W = Word> {
+    pub blocks: BTreeMap<Word, Block<W>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Block<W = Word> {
+    pub start: Word,
+    pub instructions: Vec<(Word, Instruction<W>)>,
+    pub exit: Exit,
+}
+
+// how control leaves a block
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exit {
+    // runs into the next block, which starts at this address
+    Fallthrough(Word),
+    // a jump whose condition is immediate, so always taken
+    Jump(Word),
+    // an unconditional jump after storing ret at rb+0
+    Call { target: Word, ret: Word },
+    Branch { taken: Word, fallthrough: Word },
+    // jump target comes from memory; fallthrough is None if always taken
+    Indirect { fallthrough: Option<Word> },
+    Halt,
+    // the word at this address doesn't decode, or runs off the end
+    Invalid(Word),
+}
+
+impl Exit {
+    pub fn successors(&self) -> Vec<Word> {
+        match *self {
+            Exit::Fallthrough(a) | Exit::Jump(a) => vec![a],
+            Exit::Call { target, ret } => vec![target, ret],
+            Exit::Branch { taken, fallthrough } => vec![taken, fallthrough],
+            Exit::Indirect { fallthrough } => fallthrough.into_iter().collect(),
+            Exit::Halt | Exit::Invalid(_) => vec![],
+        }
+    }
+}
+
+impl<W: Int> Block<W> {
+    // address just past the last instruction
+    pub fn end(&self) -> Word {
+        match self.instructions.last() {
+            Some((addr, inst)) => addr + inst.size() as Word,
+            None => self.start,
+        }
+    }
+}
+
+impl<W: Int> Cfg<W> {
+    pub fn build(prog: &Program<W>) -> Self {
+        Self::build_with(prog, &Operations::new())
+    }
+
+    // like build, decoding with ops instead of the built-ins. Operations
+    // other than the built-in jumps and halt are assumed to fall through.
+    pub fn build_with(prog: &Program<W>, ops: &Operations<W>) -> Self {
+        let decode = |addr: Word| -> Option<Instruction<W>> {
+            let mem = &prog.operations;
+            let fetch = |n: Word| {
+                Ok(mem
+                    .get((addr + n) as usize)
+                    .cloned()
+                    .unwrap_or_else(|| W::from_word(0)))
+            };
+            if addr < 0 {
+                return None;
+            }
+            match ops.decode(addr, fetch) {
+                Ok(inst) if addr as usize + inst.size() <= mem.len() => Some(inst),
+                _ => None,
+            }
+        };
+
+        // find every reachable instruction, and where blocks have to start
+        let mut leaders = BTreeSet::new();
+        let mut seen = HashSet::new();
+        let mut work = Vec::new();
+        if !prog.operations.is_empty() {
+            leaders.insert(0);
+            work.push(0);
+        }
+        while let Some(mut addr) = work.pop() {
+            let mut ret = None;
+            while seen.insert(addr) {
+                // as in the second pass, nothing is known at a block start
+                if leaders.contains(&addr) {
+                    ret = None;
+                }
+                let inst = match decode(addr) {
+                    Some(inst) => inst,
+                    None => break,
+                };
+                ret = track_return(&inst, ret);
+                match exit(addr, &inst, ret) {
+                    Exit::Fallthrough(next) => addr = next,
+                    other => {
+                        leaders.extend(other.successors());
+                        work.extend(other.successors());
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        for &start in &leaders {
+            let mut instructions = Vec::new();
+            let mut addr = start;
+            let mut ret = None;
+            let exit = loop {
+                let inst = match decode(addr) {
+                    Some(inst) => inst,
+                    None => break Exit::Invalid(addr),
+                };
+                ret = track_return(&inst, ret);
+                let inst_exit = exit(addr, &inst, ret);
+                instructions.push((addr, inst));
+                match inst_exit {
+                    Exit::Fallthrough(next) if leaders.contains(&next) => {
+                        break Exit::Fallthrough(next)
+                    }
+                    Exit::Fallthrough(next) => addr = next,
+                    other => break other,
+                }
+            };
+            blocks.insert(
+                start,
+                Block {
+                    start,
+                    instructions,
+                    exit,
+                },
+            );
+        }
+        Cfg { blocks }
+    }
+
+    // blocks that end in a jump through memory
+    pub fn indirect(&self) -> impl Iterator<Item = &Block<W>> {
+        self.blocks
+            .values()
+            .filter(|b| matches!(b.exit, Exit::Indirect { .. }))
+    }
+
+    // Graphviz, e.g. `dot -Tsvg cfg.dot > cfg.svg`. Branches are labelled
+    // T/F, and indirect jumps go to a shared "?" node.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        out += "digraph cfg {\n";
+        out += "  node [shape=box, fontname=\"monospace\"];\n";
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for (addr, inst) in &block.instructions {
+                write!(label, "{:04}: {}\\l", addr, escape(&inst.to_string())).unwrap();
+            }
+            if let Exit::Invalid(addr) = block.exit {
+                write!(label, "{:04}: (invalid)\\l", addr).unwrap();
+            }
+            writeln!(out, "  b{} [label=\"{}\"];", block.start, label).unwrap();
+        }
+        if self.indirect().next().is_some() {
+            out += "  indirect [shape=diamond, label=\"?\"];\n";
+        }
+        for block in self.blocks.values() {
+            let from = block.start;
+            match block.exit {
+                Exit::Fallthrough(to) | Exit::Jump(to) => {
+                    writeln!(out, "  b{} -> b{};", from, to).unwrap();
+                }
+                Exit::Call { target, ret } => {
+                    writeln!(out, "  b{} -> b{} [label=\"call\"];", from, target).unwrap();
+                    writeln!(out, "  b{} -> b{} [style=dashed];", from, ret).unwrap();
+                }
+                Exit::Branch { taken, fallthrough } => {
+                    writeln!(out, "  b{} -> b{} [label=\"T\"];", from, taken).unwrap();
+                    writeln!(out, "  b{} -> b{} [label=\"F\"];", from, fallthrough).unwrap();
+                }
+                Exit::Indirect { fallthrough } => {
+                    writeln!(out, "  b{} -> indirect [style=dashed];", from).unwrap();
+                    if let Some(to) = fallthrough {
+                        writeln!(out, "  b{} -> b{} [label=\"F\"];", from, to).unwrap();
+                    }
+                }
+                Exit::Halt | Exit::Invalid(_) => (),
+            }
+        }
+        out += "}\n";
+        out
+    }
+}
+
+// how control leaves the instruction at addr, ignoring block boundaries. ret
+// is the return address most recently stored at rb+0, if any.
+fn exit<W: Int>(addr: Word, inst: &Instruction<W>, ret: Option<Word>) -> Exit {
+    let next = addr + inst.size() as Word;
+    let op = inst.operation;
+    if op == Operation::HLT {
+        return Exit::Halt;
+    }
+    if op != Operation::JIT && op != Operation::JIF {
+        return Exit::Fallthrough(next);
+    }
+    let params = inst.params();
+    // Some(taken) if the condition is known
+    let always = match &params[0] {
+        Param::Immediate(v) => Some((*v != W::from_word(0)) == (op == Operation::JIT)),
+        _ => None,
+    };
+    // an immediate target too big for an address is left as indirect; it
+    // fails when the jump is taken
+    let target = match &params[1] {
+        Param::Immediate(to) => to.to_word(),
+        _ => None,
+    };
+    match (always, target) {
+        (Some(false), _) => Exit::Fallthrough(next),
+        (Some(true), Some(target)) => match ret {
+            Some(ret) => Exit::Call { target, ret },
+            None => Exit::Jump(target),
+        },
+        (Some(true), None) => Exit::Indirect { fallthrough: None },
+        (None, Some(to)) => Exit::Branch {
+            taken: to,
+            fallthrough: next,
+        },
+        (None, None) => Exit::Indirect {
+            fallthrough: Some(next),
+        },
+    }
+}
+
+// add/multiply of two constants into rb+0
+// ret after inst: set by storing a constant at rb+0, forgotten once the
+// relative base moves
+fn track_return<W: Int>(inst: &Instruction<W>, ret: Option<Word>) -> Option<Word> {
+    if inst.operation == Operation::RBO {
+        None
+    } else {
+        return_address(inst).or(ret)
+    }
+}
+
+fn return_address<W: Int>(inst: &Instruction<W>) -> Option<Word> {
+    match (inst.operation, inst.params()) {
+        (op, [Param::Immediate(a), Param::Immediate(b), Param::Relative(0)]) => {
+            if op == Operation::ADD {
+                a.add(b, Arithmetic::Checked)?.to_word()
+            } else if op == Operation::MUL {
+                a.mul(b, Arithmetic::Checked)?.to_word()
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,111 @@
+use super::{disasm, Int, Program, Runtime, Word};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+// The set of addresses executed as instructions, collected while coverage is
+// enabled on a Runtime. Coverage from several runs of the same program can
+// be merged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    executed: BTreeSet<Word>,
+}
+
+// Coverage measured against the program's (linear) disassembly. .data lines
+// don't count either way; executed addresses the disassembly doesn't line
+// up with are covered but not part of total.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverageSummary {
+    pub total: usize,
+    pub covered: Vec<Word>,
+    pub uncovered: Vec<Word>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add(&mut self, pc: Word) {
+        self.executed.insert(pc);
+    }
+    pub fn merge(&mut self, other: &Coverage) {
+        self.executed.extend(&other.executed);
+    }
+    pub fn is_covered(&self, pc: Word) -> bool {
+        self.executed.contains(&pc)
+    }
+    // ascending
+    pub fn executed(&self) -> impl Iterator<Item = Word> + '_ {
+        self.executed.iter().copied()
+    }
+
+    pub fn summary<W: Int>(&self, prog: &Program<W>) -> CoverageSummary {
+        let (covered, uncovered) = disasm::disassemble(prog)
+            .into_iter()
+            .filter(|l| l.instruction.is_some())
+            .map(|l| l.addr)
+            .partition::<Vec<_>, _>(|a| self.is_covered(*a));
+        CoverageSummary {
+            total: covered.len() + uncovered.len(),
+            covered: self.executed().collect(),
+            uncovered,
+        }
+    }
+
+    // disassembly with each instruction marked + (executed) or - (not)
+    //
+    //   + 0000: input [12]
+    //   - 0002: add [12], #-1, [12]
+    //     0012: .data 0
+    pub fn listing<W: Int>(&self, prog: &Program<W>) -> String {
+        let mut out = String::new();
+        for line in disasm::disassemble(prog) {
+            let mark = match (&line.instruction, self.is_covered(line.addr)) {
+                (_, true) => '+',
+                (Some(_), false) => '-',
+                (None, false) => ' ',
+            };
+            writeln!(out, "{} {:04}: {}", mark, line.addr, line.text()).unwrap();
+        }
+        out
+    }
+}
+
+impl CoverageSummary {
+    // covered instructions as a percentage of total
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+        100.0 * (self.total - self.uncovered.len()) as f64 / self.total as f64
+    }
+
+    // {"total":12,"covered":[0,2,6],"uncovered":[9,11],"percent":60.0}
+    pub fn to_json(&self) -> String {
+        let list = |v: &[Word]| {
+            v.iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        format!(
+            r#"{{"total":{},"covered":[{}],"uncovered":[{}],"percent":{:.1}}}"#,
+            self.total,
+            list(&self.covered),
+            list(&self.uncovered),
+            self.percent()
+        )
+    }
+}
+
+impl<W: Int> Runtime<W> {
+    // start (or restart) collecting coverage
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+    pub fn disable_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+}
@@ -0,0 +1,90 @@
+use super::ops::{Instruction, Operations};
+use super::{Int, Program, Word};
+
+// One line of a disassembled listing. instruction is None for words that
+// don't decode (or that run off the end of the program), which are listed
+// as .data
+#[derive(Debug, PartialEq)]
+pub struct Line<W = Word> {
+    pub addr: Word,
+    pub words: Vec<W>,
+    pub instruction: Option<Instruction<W>>,
+}
+
+impl<W: Int> Line<W> {
+    pub fn text(&self) -> String {
+        match &self.instruction {
+            Some(inst) => inst.to_string(),
+            None => format!(".data {}", self.words[0]),
+        }
+    }
+}
+
+// Linear sweep from address 0: anything that decodes is treated as code.
+pub fn disassemble<W: Int>(prog: &Program<W>) -> Vec<Line<W>> {
+    disassemble_with(prog, &Operations::new())
+}
+
+// like disassemble, decoding with ops instead of the built-ins
+pub fn disassemble_with<W: Int>(prog: &Program<W>, ops: &Operations<W>) -> Vec<Line<W>> {
+    let mem = &prog.operations;
+    let mut lines = Vec::new();
+
+    let mut addr = 0;
+    while addr < mem.len() {
+        let fetch = |n: Word| {
+            Ok(mem
+                .get(addr + n as usize)
+                .cloned()
+                .unwrap_or_else(|| W::from_word(0)))
+        };
+        let line = match ops.decode(addr as Word, fetch) {
+            Ok(inst) if addr + inst.size() <= mem.len() => Line {
+                addr: addr as Word,
+                words: mem[addr..addr + inst.size()].to_vec(),
+                instruction: Some(inst),
+            },
+            _ => Line {
+                addr: addr as Word,
+                words: vec![mem[addr].clone()],
+                instruction: None,
+            },
+        };
+        addr += line.words.len();
+        lines.push(line);
+    }
+    lines
+}
+
+// 0012: multiply [4], #3, [4]
+// or, with show_raw,
+// 0012: 1002 4 3 4          multiply [4], #3, [4]
+pub fn render<W: Int>(lines: &[Line<W>], show_raw: bool) -> String {
+    let raw: Vec<String> = lines
+        .iter()
+        .map(|l| {
+            l.words
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+    let raw_width = raw.iter().map(|r| r.len()).max().unwrap_or(0);
+
+    let mut out = String::new();
+    for (line, raw) in lines.iter().zip(raw) {
+        if show_raw {
+            out += &format!(
+                "{:04}: {:<3$}  {}\n",
+                line.addr,
+                raw,
+                line.text(),
+                raw_width
+            );
+        } else {
+            out += &format!("{:04}: {}\n", line.addr, line.text());
+        }
+    }
+    out
+}
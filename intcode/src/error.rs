@@ -0,0 +1,264 @@
+use super::{Breakpoint, Int, Word};
+use std::fmt;
+use std::num::ParseIntError;
+use std::time::Duration;
+
+// W is the machine's value type; see Int
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntcodeError<W = Word> {
+    // program text contained a token that isn't a number; index counts
+    // words, offset is in bytes
+    Parse {
+        index: usize,
+        offset: usize,
+        token: String,
+        source: ParseIntError,
+    },
+    // assembler input was malformed; line and column are 1-based
+    Assemble {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    // the low two digits of the instruction word aren't a known opcode
+    UnknownOpcode {
+        pc: Word,
+        word: W,
+        opcode: Word,
+    },
+    // a parameter mode digit other than 0, 1 or 2
+    UnknownParamMode {
+        pc: Word,
+        word: W,
+        param: usize,
+        mode: Word,
+    },
+    // an instruction tried to write through an immediate-mode parameter
+    ImmediateWrite {
+        pc: Word,
+        word: W,
+        opcode: Word,
+        param: usize,
+    },
+    // a read, write or fetch from an address below zero
+    NegativeAddress {
+        pc: Word,
+        addr: Word,
+        relative_base: Word,
+    },
+    // a value used as an address, jump target or relative base offset is
+    // too big for a Word, or an offset (the value) from the pc or relative
+    // base goes past the largest Word
+    AddressOutOfRange {
+        pc: Word,
+        value: W,
+    },
+    // with checked arithmetic, the add or multiply at pc overflowed
+    Overflow {
+        pc: Word,
+        opcode: Word,
+        lhs: W,
+        rhs: W,
+    },
+    // with self-modification checks set to error, the instruction at pc
+    // wrote to addr, which has already been executed as code
+    SelfModifyingCode {
+        pc: Word,
+        addr: Word,
+    },
+    // resume() was called after the program halted
+    Halted,
+    // the program is blocked on input and resume() wasn't given a value
+    MissingInput {
+        pc: Word,
+    },
+    // a helper expected input or halt, but the program produced a value
+    UnexpectedOutput {
+        value: W,
+    },
+    // a helper expected an output, but the program asked for input
+    UnexpectedInput,
+    // a helper expected input or output, but the program halted
+    UnexpectedHalt,
+    // a helper that doesn't handle breakpoints ran into one
+    UnexpectedBreak {
+        breakpoint: Breakpoint,
+    },
+    // an io::Input had nothing left to give
+    InputExhausted,
+    // io::AsciiInput was given text with a character outside ASCII
+    NonAscii {
+        ch: char,
+    },
+    // a channel io::Input waited this long without getting a value
+    InputTimeout {
+        timeout: Duration,
+    },
+    // a channel io::Output couldn't deliver a value within this long
+    OutputTimeout {
+        timeout: Duration,
+    },
+    // a helper ran out of fuel, or past its deadline, after executing this
+    // many instructions in total
+    OutOfFuel {
+        executed: u64,
+    },
+    // the other end of a channel io::Input or io::Output went away
+    Disconnected,
+    // no machine in a Network can make progress; these are still waiting
+    // for input
+    Deadlock {
+        waiting: Vec<String>,
+    },
+    // an io::Input/io::Output method that the implementation doesn't support
+    NotImplemented,
+    // a saved snapshot couldn't be understood; line is 1-based, or 0 for
+    // problems with the snapshot as a whole
+    Snapshot {
+        line: usize,
+        message: String,
+    },
+    // a binary program or memory image couldn't be decoded; offset is in
+    // bytes
+    Binary {
+        offset: usize,
+        message: String,
+    },
+    // reading or writing a file failed
+    Io(String),
+    // escape hatch for io implementations outside this crate
+    Other(String),
+    // an error with some extra context, see ErrWrapExt::wrap
+    Context {
+        context: String,
+        source: Box<IntcodeError<W>>,
+    },
+}
+
+impl<W: Int> fmt::Display for IntcodeError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use IntcodeError::*;
+        match self {
+            Parse {
+                index,
+                offset,
+                token,
+                ..
+            } => write!(
+                f,
+                "invalid word '{}' at index {} (byte {})",
+                token, index, offset
+            ),
+            Assemble {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            UnknownOpcode { pc, word, opcode } => {
+                write!(f, "no such opcode {} (word {}) at pc={}", opcode, word, pc)
+            }
+            UnknownParamMode {
+                pc,
+                word,
+                param,
+                mode,
+            } => write!(
+                f,
+                "unknown mode {} for param {} (word {}) at pc={}",
+                mode, param, word, pc
+            ),
+            ImmediateWrite {
+                pc,
+                word,
+                opcode,
+                param,
+            } => write!(
+                f,
+                "opcode {} cannot write to immediate param {} (word {}) at pc={}",
+                opcode, param, word, pc
+            ),
+            NegativeAddress {
+                pc,
+                addr,
+                relative_base,
+            } => write!(
+                f,
+                "negative address {} at pc={} (rb={})",
+                addr, pc, relative_base
+            ),
+            Overflow {
+                pc,
+                opcode,
+                lhs,
+                rhs,
+            } => write!(
+                f,
+                "overflow in opcode {} with {} and {} at pc={}",
+                opcode, lhs, rhs, pc
+            ),
+            AddressOutOfRange { pc, value } => {
+                write!(f, "{} is out of range for an address at pc={}", value, pc)
+            }
+            SelfModifyingCode { pc, addr } => write!(
+                f,
+                "write to already executed code at {} from pc={}",
+                addr, pc
+            ),
+            Halted => write!(f, "cannot resume, program complete"),
+            MissingInput { pc } => write!(f, "expected a value to resume input at pc={}", pc),
+            UnexpectedOutput { value } => write!(f, "unexpected output: {}", value),
+            UnexpectedInput => write!(f, "unexpected ask for input"),
+            UnexpectedHalt => write!(f, "unexpected complete"),
+            UnexpectedBreak { breakpoint } => write!(f, "unexpected break at {}", breakpoint),
+            InputExhausted => write!(f, "no more input"),
+            NonAscii { ch } => write!(f, "{:?} is not ASCII", ch),
+            InputTimeout { timeout } => write!(f, "no input within {:?}", timeout),
+            OutputTimeout { timeout } => write!(f, "output not accepted within {:?}", timeout),
+            OutOfFuel { executed } => {
+                write!(f, "out of fuel after {} instructions", executed)
+            }
+            Disconnected => write!(f, "channel disconnected"),
+            Deadlock { waiting } => {
+                write!(f, "deadlock, waiting for input: {}", waiting.join(", "))
+            }
+            NotImplemented => write!(f, "not implemented"),
+            Snapshot { line, message } => write!(f, "snapshot line {}: {}", line, message),
+            Binary { offset, message } => write!(f, "binary byte {}: {}", offset, message),
+            Io(s) => write!(f, "io error: {}", s),
+            Other(s) => write!(f, "{}", s),
+            Context { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl<W: Int> std::error::Error for IntcodeError<W> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IntcodeError::Parse { source, .. } => Some(source),
+            IntcodeError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl<W> IntcodeError<W> {
+    // the innermost error, skipping any context wrappers
+    pub fn root(&self) -> &IntcodeError<W> {
+        match self {
+            IntcodeError::Context { source, .. } => source.root(),
+            e => e,
+        }
+    }
+}
+
+pub(crate) trait ErrWrapExt<T, W> {
+    fn wrap(self, s: impl fmt::Display) -> Result<T, IntcodeError<W>>;
+}
+impl<T, W> ErrWrapExt<T, W> for Result<T, IntcodeError<W>> {
+    fn wrap(self, s: impl fmt::Display) -> Result<T, IntcodeError<W>> {
+        self.map_err(|e| IntcodeError::Context {
+            context: s.to_string(),
+            source: Box::new(e),
+        })
+    }
+}
@@ -1,40 +1,42 @@
-use super::Word;
+use super::{Int, IntcodeError, Word};
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
 
-pub trait Input {
-  fn read(&mut self) -> Result<Word, String>;
+pub trait Input<W = Word> {
+  fn read(&mut self) -> Result<W, IntcodeError<W>>;
 }
-pub trait Output {
-  fn write(&mut self, val: Word) -> Result<(), String>;
+pub trait Output<W = Word> {
+  fn write(&mut self, val: W) -> Result<(), IntcodeError<W>>;
 }
 
 pub struct NotImplemented;
-impl Input for NotImplemented {
-  fn read(&mut self) -> Result<Word, String> {
-    Err("Not implemented".to_string())
+impl<W> Input<W> for NotImplemented {
+  fn read(&mut self) -> Result<W, IntcodeError<W>> {
+    Err(IntcodeError::NotImplemented)
   }
 }
-impl Output for NotImplemented {
-  fn write(&mut self, _: Word) -> Result<(), String> {
-    Err("Not implemented".to_string())
+impl<W> Output<W> for NotImplemented {
+  fn write(&mut self, _: W) -> Result<(), IntcodeError<W>> {
+    Err(IntcodeError::NotImplemented)
   }
 }
 
-pub struct IteratorInput<I, W>
+pub struct IteratorInput<I, V>
 where
-  I: Iterator<Item = W>,
-  W: Into<Word>,
+  I: Iterator<Item = V>,
 {
   iter: I,
 }
 
-impl<I, W> IteratorInput<I, W>
+impl<I, V> IteratorInput<I, V>
 where
-  I: Iterator<Item = W>,
-  W: Into<Word>,
+  I: Iterator<Item = V>,
 {
   pub fn new<II>(i: II) -> Self
   where
-    II: IntoIterator<IntoIter = I, Item = W>,
+    II: IntoIterator<IntoIter = I, Item = V>,
   {
     Self {
       iter: i.into_iter(),
@@ -42,43 +44,313 @@ where
   }
 }
 
-impl<I, W> Input for IteratorInput<I, W>
+impl<I, V, W> Input<W> for IteratorInput<I, V>
 where
-  I: Iterator<Item = W>,
-  W: Into<Word>,
+  I: Iterator<Item = V>,
+  V: Into<W>,
 {
-  fn read(&mut self) -> Result<Word, String> {
+  fn read(&mut self) -> Result<W, IntcodeError<W>> {
     match self.iter.next() {
       Some(x) => Ok(x.into()),
-      None => Err("No more input".to_string()),
+      None => Err(IntcodeError::InputExhausted),
     }
   }
 }
 
+#[derive(Default)]
 pub struct StdoutOutput;
 impl StdoutOutput {
   pub fn new() -> Self {
     StdoutOutput {}
   }
 }
-impl Output for StdoutOutput {
-  fn write(&mut self, val: Word) -> Result<(), String> {
+impl<W: Int> Output<W> for StdoutOutput {
+  fn write(&mut self, val: W) -> Result<(), IntcodeError<W>> {
     println!("{}", val);
     Ok(())
   }
 }
 
-pub struct VecOutput<'a> {
-  vec: &'a mut Vec<Word>,
+pub struct VecOutput<'a, W = Word> {
+  vec: &'a mut Vec<W>,
 }
-impl<'a> VecOutput<'a> {
-  pub fn new(vec: &'a mut Vec<Word>) -> Self {
+impl<'a, W> VecOutput<'a, W> {
+  pub fn new(vec: &'a mut Vec<W>) -> Self {
     Self { vec }
   }
 }
-impl Output for VecOutput<'_> {
-  fn write(&mut self, val: Word) -> Result<(), String> {
+impl<W> Output<W> for VecOutput<'_, W> {
+  fn write(&mut self, val: W) -> Result<(), IntcodeError<W>> {
     self.vec.push(val);
     Ok(())
   }
 }
+
+// Feeds text to programs that read ASCII, one character code per read.
+// Text given up front is used first, then the reader (if any) a line at a
+// time, so an interactive reader is only asked once the program wants more.
+pub struct AsciiInput {
+  pending: VecDeque<char>,
+  reader: Option<Box<dyn BufRead>>,
+}
+impl AsciiInput {
+  pub fn new(text: &str) -> Self {
+    Self {
+      pending: text.chars().collect(),
+      reader: None,
+    }
+  }
+  pub fn from_reader<R: BufRead + 'static>(reader: R) -> Self {
+    Self {
+      pending: VecDeque::new(),
+      reader: Some(Box::new(reader)),
+    }
+  }
+  // queue more text after anything not yet read
+  pub fn push_str(&mut self, text: &str) {
+    self.pending.extend(text.chars());
+  }
+}
+impl<W: Int> Input<W> for AsciiInput {
+  fn read(&mut self) -> Result<W, IntcodeError<W>> {
+    if self.pending.is_empty() {
+      if let Some(reader) = &mut self.reader {
+        let mut line = String::new();
+        reader
+          .read_line(&mut line)
+          .map_err(|e| IntcodeError::Io(e.to_string()))?;
+        self.push_str(&line);
+      }
+    }
+    next_char(&mut self.pending)
+  }
+}
+
+pub(crate) fn next_char<W: Int>(pending: &mut VecDeque<char>) -> Result<W, IntcodeError<W>> {
+  match pending.pop_front() {
+    Some(ch) if ch.is_ascii() => Ok(W::from_word(ch as Word)),
+    Some(ch) => Err(IntcodeError::NonAscii { ch }),
+    None => Err(IntcodeError::InputExhausted),
+  }
+}
+
+// Input typed at the console, for running programs by hand. By default each
+// line is parsed as one number, asking again if it isn't one; in ascii mode
+// whole lines are sent as character codes, newline included. The prompt is
+// shown whenever another line is needed, and end of input (ctrl-D) is
+// InputExhausted.
+pub struct StdinInput {
+  prompt: String,
+  ascii: bool,
+  pending: VecDeque<char>,
+}
+impl Default for StdinInput {
+  fn default() -> Self {
+    Self {
+      prompt: "> ".to_string(),
+      ascii: false,
+      pending: VecDeque::new(),
+    }
+  }
+}
+impl StdinInput {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  pub fn ascii() -> Self {
+    Self {
+      ascii: true,
+      ..Self::default()
+    }
+  }
+  pub fn with_prompt(mut self, prompt: &str) -> Self {
+    self.prompt = prompt.to_string();
+    self
+  }
+  fn line<W>(&self) -> Result<String, IntcodeError<W>> {
+    let io_err = |e: std::io::Error| IntcodeError::Io(e.to_string());
+    let mut stdout = std::io::stdout();
+    write!(stdout, "{}", self.prompt).map_err(io_err)?;
+    stdout.flush().map_err(io_err)?;
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line).map_err(io_err)? {
+      0 => Err(IntcodeError::InputExhausted),
+      _ => Ok(line),
+    }
+  }
+}
+impl<W: Int> Input<W> for StdinInput {
+  fn read(&mut self) -> Result<W, IntcodeError<W>> {
+    if self.ascii {
+      if self.pending.is_empty() {
+        let line = self.line()?;
+        self.pending.extend(line.chars());
+      }
+      return next_char(&mut self.pending);
+    }
+    loop {
+      let line = self.line()?;
+      let line = line.trim();
+      match W::parse(line) {
+        Ok(val) => return Ok(val),
+        Err(_) if line.is_empty() => (),
+        Err(e) => eprintln!("{:?} isn't a number: {}", line, e),
+      }
+    }
+  }
+}
+
+// Collects output from programs that write ASCII. Values outside 0..=127,
+// usually the final answer after a transcript, are kept separately.
+pub struct AsciiOutput<W = Word> {
+  text: String,
+  numbers: Vec<W>,
+}
+impl<W> Default for AsciiOutput<W> {
+  fn default() -> Self {
+    Self {
+      text: String::new(),
+      numbers: Vec::new(),
+    }
+  }
+}
+impl<W> AsciiOutput<W> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  pub fn text(&self) -> &str {
+    &self.text
+  }
+  pub fn numbers(&self) -> &[W] {
+    &self.numbers
+  }
+  pub fn into_parts(self) -> (String, Vec<W>) {
+    (self.text, self.numbers)
+  }
+}
+impl<W: Int> Output<W> for AsciiOutput<W> {
+  fn write(&mut self, val: W) -> Result<(), IntcodeError<W>> {
+    match val.to_word() {
+      Some(c @ 0..=127) => self.text.push(c as u8 as char),
+      _ => self.numbers.push(val),
+    }
+    Ok(())
+  }
+}
+
+// A connected output/input pair, for wiring a machine's output to another
+// machine's input when each runs on its own thread.
+pub fn channel<W>() -> (ChannelOutput<W>, ChannelInput<W>) {
+  let (tx, rx) = mpsc::channel();
+  (ChannelOutput::new(tx), ChannelInput::new(rx))
+}
+
+// Blocks until a value arrives. With a timeout, waiting longer than that is
+// an InputTimeout error; without one it can wait forever.
+pub struct ChannelInput<W = Word> {
+  rx: Receiver<W>,
+  timeout: Option<Duration>,
+}
+impl<W> ChannelInput<W> {
+  pub fn new(rx: Receiver<W>) -> Self {
+    Self { rx, timeout: None }
+  }
+  pub fn with_timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = Some(timeout);
+    self
+  }
+  // get the receiver back, e.g. to collect values sent after the machine halted
+  pub fn into_inner(self) -> Receiver<W> {
+    self.rx
+  }
+}
+impl<W> Input<W> for ChannelInput<W> {
+  fn read(&mut self) -> Result<W, IntcodeError<W>> {
+    match self.timeout {
+      None => self.rx.recv().map_err(|_| IntcodeError::Disconnected),
+      Some(timeout) => self.rx.recv_timeout(timeout).map_err(|e| match e {
+        RecvTimeoutError::Timeout => IntcodeError::InputTimeout { timeout },
+        RecvTimeoutError::Disconnected => IntcodeError::Disconnected,
+      }),
+    }
+  }
+}
+
+// Unbounded, so writing never blocks
+pub struct ChannelOutput<W = Word> {
+  tx: Sender<W>,
+}
+impl<W> ChannelOutput<W> {
+  pub fn new(tx: Sender<W>) -> Self {
+    Self { tx }
+  }
+}
+impl<W> Output<W> for ChannelOutput<W> {
+  fn write(&mut self, val: W) -> Result<(), IntcodeError<W>> {
+    self.tx.send(val).map_err(|_| IntcodeError::Disconnected)
+  }
+}
+
+// The same over crossbeam channels, which can also be bounded, in which case
+// output can time out too.
+#[cfg(feature = "crossbeam")]
+pub mod crossbeam {
+  use super::{Input, Output};
+  use crate::{IntcodeError, Word};
+  use crossbeam_channel::{Receiver, RecvTimeoutError, SendTimeoutError, Sender};
+  use std::time::Duration;
+
+  pub struct CrossbeamInput<W = Word> {
+    rx: Receiver<W>,
+    timeout: Option<Duration>,
+  }
+  impl<W> CrossbeamInput<W> {
+    pub fn new(rx: Receiver<W>) -> Self {
+      Self { rx, timeout: None }
+    }
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+      self.timeout = Some(timeout);
+      self
+    }
+    pub fn into_inner(self) -> Receiver<W> {
+      self.rx
+    }
+  }
+  impl<W> Input<W> for CrossbeamInput<W> {
+    fn read(&mut self) -> Result<W, IntcodeError<W>> {
+      match self.timeout {
+        None => self.rx.recv().map_err(|_| IntcodeError::Disconnected),
+        Some(timeout) => self.rx.recv_timeout(timeout).map_err(|e| match e {
+          RecvTimeoutError::Timeout => IntcodeError::InputTimeout { timeout },
+          RecvTimeoutError::Disconnected => IntcodeError::Disconnected,
+        }),
+      }
+    }
+  }
+
+  pub struct CrossbeamOutput<W = Word> {
+    tx: Sender<W>,
+    timeout: Option<Duration>,
+  }
+  impl<W> CrossbeamOutput<W> {
+    pub fn new(tx: Sender<W>) -> Self {
+      Self { tx, timeout: None }
+    }
+    // only matters for bounded channels
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+      self.timeout = Some(timeout);
+      self
+    }
+  }
+  impl<W> Output<W> for CrossbeamOutput<W> {
+    fn write(&mut self, val: W) -> Result<(), IntcodeError<W>> {
+      match self.timeout {
+        None => self.tx.send(val).map_err(|_| IntcodeError::Disconnected),
+        Some(timeout) => self.tx.send_timeout(val, timeout).map_err(|e| match e {
+          SendTimeoutError::Timeout(_) => IntcodeError::OutputTimeout { timeout },
+          SendTimeoutError::Disconnected(_) => IntcodeError::Disconnected,
+        }),
+      }
+    }
+  }
+}
@@ -0,0 +1,177 @@
+use super::ops::Operation;
+use super::{Breakpoint, Int, Runtime, RuntimeState, Word};
+use std::collections::VecDeque;
+
+// Opt-in record of executed instructions, enough to undo them one at a time.
+// Enable with Runtime::enable_journal; it isn't included in snapshots.
+#[derive(Clone, Debug)]
+pub struct Journal<W = Word> {
+    entries: VecDeque<JournalEntry<W>>,
+    // oldest entries are dropped past this many
+    limit: Option<usize>,
+    // the instruction being executed right now
+    current: Option<JournalEntry<W>>,
+    // the last entry is an input instruction still waiting for its value
+    awaiting_input: bool,
+}
+
+impl<W> Journal<W> {
+    fn push(&mut self, e: JournalEntry<W>) {
+        self.entries.push_back(e);
+        if let Some(limit) = self.limit {
+            while self.entries.len() > limit {
+                self.entries.pop_front();
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalEntry<W = Word> {
+    // where the instruction was, and the relative base before it ran
+    pub pc: Word,
+    pub relative_base: Word,
+    // (addr, previous value) for every write, in order
+    pub writes: Vec<(Word, W)>,
+    pub io: Option<IoEvent<W>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IoEvent<W = Word> {
+    Input(W),
+    Output(W),
+}
+
+impl<W: Int> Runtime<W> {
+    pub fn enable_journal(&mut self, limit: Option<usize>) {
+        let mut j = Journal {
+            entries: VecDeque::new(),
+            limit,
+            current: None,
+            awaiting_input: false,
+        };
+        // Already waiting for input, so the input instruction has run but its
+        // write comes with the next resume. pc has moved past it; this assumes
+        // it was a built-in input, not a custom op that reads.
+        if self.read_addr.is_some() {
+            j.entries.push_back(JournalEntry {
+                pc: self.pc - (Operation::<W>::INP.params as Word + 1),
+                relative_base: self.relative_base,
+                writes: Vec::new(),
+                io: None,
+            });
+            j.awaiting_input = true;
+        }
+        self.journal = Some(j);
+    }
+    pub fn disable_journal(&mut self) {
+        self.journal = None;
+    }
+    // oldest first
+    pub fn journal(&self) -> impl Iterator<Item = &JournalEntry<W>> {
+        self.journal.iter().flat_map(|j| j.entries.iter())
+    }
+
+    // the most recent journaled instruction that wrote to addr
+    pub fn last_writer(&self, addr: Word) -> Option<&JournalEntry<W>> {
+        self.journal
+            .as_ref()?
+            .entries
+            .iter()
+            .rev()
+            .find(|e| e.writes.iter().any(|(a, _)| *a == addr))
+    }
+
+    // Undo the last journaled instruction, leaving pc on it and the runtime
+    // paused (Break(Step)) so resume re-executes it. Returns the undone entry,
+    // or None if there's nothing left to undo.
+    pub fn step_back(&mut self) -> Option<JournalEntry<W>> {
+        let j = self.journal.as_mut()?;
+        let entry = j.entries.pop_back()?;
+        j.awaiting_input = false;
+        for (addr, old) in entry.writes.iter().rev() {
+            self.mem.set(*addr as usize, old.clone());
+        }
+        self.pc = entry.pc;
+        self.relative_base = entry.relative_base;
+        self.read_addr = None;
+        self.watch_hit.set(None);
+        self.skip_break = true;
+        self.state = Some(RuntimeState::Break(Breakpoint::Step));
+        Some(entry)
+    }
+
+    // step back until an input or output instruction has been undone, and
+    // return what it read or wrote
+    pub fn rewind_to_io(&mut self) -> Option<IoEvent<W>> {
+        loop {
+            if let Some(io) = self.step_back()?.io {
+                return Some(io);
+            }
+        }
+    }
+
+    pub(crate) fn journal_begin(&mut self) {
+        if let Some(j) = &mut self.journal {
+            j.current = Some(JournalEntry {
+                pc: self.pc,
+                relative_base: self.relative_base,
+                writes: Vec::new(),
+                io: None,
+            });
+        }
+    }
+
+    pub(crate) fn journal_write(&mut self, addr: usize) {
+        if let Some(Journal {
+            current: Some(e), ..
+        }) = &mut self.journal
+        {
+            e.writes.push((addr as Word, self.mem.get(addr)));
+        }
+    }
+
+    pub(crate) fn journal_end(&mut self) {
+        if let Some(j) = &mut self.journal {
+            if let Some(mut e) = j.current.take() {
+                if let Some(RuntimeState::Resumable(Some(x))) = &self.state {
+                    e.io = Some(IoEvent::Output(x.clone()));
+                }
+                j.awaiting_input = e.io.is_none() && self.read_addr.is_some();
+                j.push(e);
+            }
+        }
+    }
+
+    // The instruction failed. Its entry is kept if there's a write to undo,
+    // or it's an input still waiting for a value that can be sent again.
+    pub(crate) fn journal_abort(&mut self) {
+        if let Some(j) = &mut self.journal {
+            if let Some(e) = j.current.take() {
+                if j.awaiting_input || !e.writes.is_empty() {
+                    j.push(e);
+                }
+            }
+        }
+    }
+
+    // the value for an input instruction arrives on the following resume;
+    // fold its write back into that instruction's entry
+    pub(crate) fn journal_input_begin(&mut self) {
+        if let Some(j) = &mut self.journal {
+            if j.awaiting_input {
+                j.current = j.entries.pop_back();
+            }
+        }
+    }
+
+    pub(crate) fn journal_input_end(&mut self, val: W) {
+        if let Some(Journal {
+            current: Some(e), ..
+        }) = &mut self.journal
+        {
+            e.io = Some(IoEvent::Input(val));
+        }
+        self.journal_end();
+    }
+}
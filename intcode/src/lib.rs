@@ -1,49 +1,105 @@
+pub mod asm;
+#[cfg(feature = "async")]
+mod async_io;
+mod binary;
+mod budget;
+pub mod cfg;
+mod coverage;
+pub mod disasm;
+mod error;
 pub mod io;
-mod ops;
+mod journal;
+mod load;
+mod mem;
+pub mod network;
+pub mod ops;
+mod profile;
+pub mod session;
+mod smc;
+mod snapshot;
 #[cfg(test)]
 mod tests;
+pub mod trace;
+mod word;
 
-use std::collections::HashMap;
+pub use coverage::{Coverage, CoverageSummary};
+use error::ErrWrapExt;
+pub use error::IntcodeError;
+pub use journal::{IoEvent, Journal, JournalEntry};
+use mem::Memory;
+pub use profile::{HotLoop, Profile};
+pub use smc::{CodeWrite, SmcMode};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use trace::{TraceEvent, TraceSink};
+pub use word::Int;
 
 pub type Word = i64;
 
-pub struct Program {
-    operations: Vec<Word>,
+pub struct Program<W = Word> {
+    operations: Vec<W>,
 }
 
+// Constructors for the default Word type. For other Int types, use
+// Program::from(words) or str::parse.
 impl Program {
     pub fn new(mem: Vec<Word>) -> Self {
-        Program {
-            operations: mem.clone(),
-        }
+        Program::from(mem)
+    }
+    pub fn parse(input: &str) -> Result<Self, IntcodeError> {
+        input.parse()
+    }
+    pub fn assemble(src: &str) -> Result<Self, IntcodeError> {
+        Ok(asm::assemble(src)?.program)
+    }
+}
+
+impl<W: Int> From<Vec<W>> for Program<W> {
+    fn from(operations: Vec<W>) -> Self {
+        Program { operations }
     }
-    pub fn parse(input: &str) -> Result<Self, String> {
-        let parsed = input
-            .trim()
-            .split(",")
-            .into_iter()
-            .map(|s| s.parse::<Word>())
-            .collect::<Result<Vec<_>, _>>()
-            .or_else(|e| Err(format!("{}: '{}'", e.to_string(), input)))?;
-        Ok(Self::new(parsed))
-    }
-    pub fn run(&self, noun: Word, verb: Word) -> Result<Word, String> {
+}
+
+impl<W: Int> std::str::FromStr for Program<W> {
+    type Err = IntcodeError<W>;
+
+    // see load.rs for the format
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Ok(Program::from(load::parse_words(input)?))
+    }
+}
+
+impl<W: Int> Program<W> {
+    pub fn run(&self, noun: W, verb: W) -> Result<W, IntcodeError<W>> {
         let mut rt = self.new_runtime();
         rt.set(1, noun)?;
         rt.set(2, verb)?;
         rt.resume(None)?;
         rt.get(0)
     }
+    // like run, but give up with IntcodeError::OutOfFuel after executing fuel
+    // instructions, for inputs that might never halt
+    pub fn run_limited(&self, noun: W, verb: W, fuel: u64) -> Result<W, IntcodeError<W>> {
+        let mut rt = self.new_runtime();
+        rt.set_fuel(Some(fuel));
+        rt.set(1, noun)?;
+        rt.set(2, verb)?;
+        if let RuntimeState::OutOfFuel = rt.resume(None)? {
+            return Err(rt.out_of_fuel());
+        }
+        rt.get(0)
+    }
 
     pub fn run_io(
         &self,
-        input: &mut dyn io::Input,
-        output: &mut dyn io::Output,
-    ) -> Result<(), String> {
+        input: &mut dyn io::Input<W>,
+        output: &mut dyn io::Output<W>,
+    ) -> Result<(), IntcodeError<W>> {
         self.new_runtime().run_io(input, output)
     }
 
-    pub fn run_collect_output(&self, input: Vec<Word>) -> Result<Vec<Word>, String> {
+    pub fn run_collect_output(&self, input: Vec<W>) -> Result<Vec<W>, IntcodeError<W>> {
         let mut i = io::IteratorInput::new(input);
         let mut v = Vec::new();
         let mut o = io::VecOutput::new(&mut v);
@@ -51,14 +107,23 @@ impl Program {
         Ok(v)
     }
 
+    // for programs that talk in ASCII: input is sent as character codes, and
+    // returns the text output along with any values that aren't ASCII
+    pub fn run_ascii(&self, input: &str) -> Result<(String, Vec<W>), IntcodeError<W>> {
+        let mut i = io::AsciiInput::new(input);
+        let mut o = io::AsciiOutput::new();
+        self.run_io(&mut i, &mut o)?;
+        Ok(o.into_parts())
+    }
+
     pub fn run_loop<F>(
         &self,
-        inputs: Vec<Word>,
+        inputs: Vec<W>,
         n_outputs: usize,
         mut handler: F,
-    ) -> Result<(), String>
+    ) -> Result<(), IntcodeError<W>>
     where
-        F: FnMut(Vec<Word>) -> Vec<Word>,
+        F: FnMut(Vec<W>) -> Vec<W>,
     {
         let mut rt = self.new_runtime();
         let mut curr_inputs = inputs.clone();
@@ -70,163 +135,442 @@ impl Program {
         Ok(())
     }
 
-    pub fn new_runtime(&self) -> Runtime {
-        Runtime::new(self.operations.clone())
+    pub fn disassemble(&self) -> Vec<disasm::Line<W>> {
+        disasm::disassemble(self)
+    }
+
+    pub fn cfg(&self) -> cfg::Cfg<W> {
+        cfg::Cfg::build(self)
+    }
+
+    pub fn new_runtime(&self) -> Runtime<W> {
+        Runtime::from(self.operations.clone())
     }
 }
 
-pub struct Runtime {
-    mem: HashMap<Word, Word>,
+// Cloning is a full copy of the machine (breakpoints included), so it can be
+// used to fork execution, e.g. at an input prompt.
+#[derive(Clone)]
+pub struct Runtime<W = Word> {
+    mem: Memory<W>,
     pc: Word,
-    state: Option<RuntimeState>,
+    state: Option<RuntimeState<W>>,
     jump: Option<Word>,
-    ops: ops::Operations,
+    ops: ops::Operations<W>,
     read_addr: Option<Word>,
     relative_base: Word,
 
-    pub trace: bool,
-    pub trace_state: bool,
+    breakpoints: HashSet<Breakpoint>,
+    // a watchpoint hit during the current instruction, reported once it completes
+    watch_hit: Cell<Option<Breakpoint>>,
+    // set while an instruction, or the input value for one, is executing;
+    // other reads and writes (the caller's own get and set) don't hit
+    // watchpoints
+    executing: bool,
+    // set when stopped at a pc/opcode breakpoint, so resuming executes that instruction
+    skip_break: bool,
+    single_step: bool,
+    journal: Option<Journal<W>>,
+    // RefCell, since reads (via &self) are counted too
+    profile: Option<RefCell<Profile>>,
+    coverage: Option<Coverage>,
+    smc: Option<smc::SmcDetector<W>>,
+    // shared, so clones of a runtime trace to the same place
+    tracer: Option<Arc<Mutex<dyn TraceSink<W> + Send>>>,
+    budget: budget::Budget,
+    executed: u64,
+    arithmetic: ops::Arithmetic,
+
     pub id: String,
 }
 
 impl Runtime {
+    // for other Int types, use Runtime::from(words)
     pub fn new(initial: Vec<Word>) -> Runtime {
-        let mut m = HashMap::new();
-        m.reserve(initial.len());
+        Runtime::from(initial)
+    }
+}
+
+impl<W: Int> From<Vec<W>> for Runtime<W> {
+    fn from(initial: Vec<W>) -> Self {
         Runtime {
-            mem: initial.iter().enumerate().fold(m, |mut m, (i, w)| {
-                m.insert(i as Word, *w);
-                m
-            }),
+            mem: Memory::new(initial),
             pc: 0,
             state: None,
             jump: None,
             ops: ops::Operations::new(),
             read_addr: None,
-            trace: false,
-            trace_state: false,
+            breakpoints: HashSet::new(),
+            watch_hit: Cell::new(None),
+            executing: false,
+            skip_break: false,
+            single_step: false,
+            journal: None,
+            profile: None,
+            coverage: None,
+            smc: None,
+            tracer: None,
+            budget: Default::default(),
+            executed: 0,
+            arithmetic: ops::Arithmetic::default(),
             relative_base: 0,
             id: "".to_string(),
         }
     }
-    pub fn state(&self) -> RuntimeState {
-        self.state.unwrap()
+}
+
+impl<W: Int> Runtime<W> {
+    pub fn state(&self) -> RuntimeState<W> {
+        self.state.clone().unwrap()
     }
-    pub fn set(&mut self, addr: Word, val: Word) -> Result<(), String> {
-        if self.trace {
-            println!("{}     set(addr={}, val={})", self.id, addr, val);
-        }
-        self.mem.insert(addr, val);
+    pub fn pc(&self) -> Word {
+        self.pc
+    }
+    pub fn relative_base(&self) -> Word {
+        self.relative_base
+    }
+    pub fn set_relative_base(&mut self, rb: Word) {
+        self.relative_base = rb;
+    }
+    // how add and multiply handle overflow; checked by default
+    pub fn arithmetic(&self) -> ops::Arithmetic {
+        self.arithmetic
+    }
+    pub fn set_arithmetic(&mut self, arithmetic: ops::Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+    // the instruction set; the built-ins unless replaced by set_operations
+    pub fn operations(&self) -> &ops::Operations<W> {
+        &self.ops
+    }
+    // Takes effect from the next instruction. Snapshots don't record the
+    // operations, so set them again after restoring one.
+    pub fn set_operations(&mut self, ops: ops::Operations<W>) {
+        self.ops = ops;
+    }
+    // read memory without tracing or triggering watchpoints
+    pub fn peek(&self, addr: Word) -> Result<W, IntcodeError<W>> {
+        Ok(self.mem.get(self.addr(addr)?))
+    }
+    pub fn set(&mut self, addr: Word, val: W) -> Result<(), IntcodeError<W>> {
+        self.trace(|| TraceEvent::Write {
+            addr,
+            val: val.clone(),
+        });
+        self.watch(Breakpoint::Write(addr));
+        self.profile_write(addr);
+        let addr = self.addr(addr)?;
+        self.smc_write(addr as Word, &val)?;
+        self.journal_write(addr);
+        self.mem.set(addr, val);
         Ok(())
     }
-    pub fn get(&self, addr: Word) -> Result<Word, String> {
-        let val = *self.mem.get(&addr).unwrap_or(&0);
-        if self.trace {
-            println!("{}     get(addr={}) -> {}", self.id, addr, val);
-        }
+    pub fn get(&self, addr: Word) -> Result<W, IntcodeError<W>> {
+        self.watch(Breakpoint::Read(addr));
+        let val = self.mem.get(self.addr(addr)?);
+        self.trace(|| TraceEvent::Read {
+            addr,
+            val: val.clone(),
+        });
+        self.profile_read(addr);
         Ok(val)
     }
-    pub fn get_word(&self, n: Word) -> Result<Word, String> {
-        let val = *self.mem.get(&(self.pc + n)).unwrap_or(&0);
-        // if self.trace && !self.trace_state {
-        //     println!(
-        //         "{}     get_word(pc={}, n={}) -> {}",
-        //         self.id, self.pc, n, val
-        //     );
-        // }
-        Ok(val)
+    pub fn get_word(&self, n: Word) -> Result<W, IntcodeError<W>> {
+        Ok(self.mem.get(self.addr(self.offset(self.pc, n)?)?))
+    }
+
+    pub fn set_tracer<S: TraceSink<W> + Send + 'static>(&mut self, sink: S) {
+        self.tracer = Some(Arc::new(Mutex::new(sink)));
+    }
+    // for a sink the caller wants to keep hold of, e.g. to inspect a RingBuffer
+    pub fn set_shared_tracer(&mut self, sink: Arc<Mutex<dyn TraceSink<W> + Send>>) {
+        self.tracer = Some(sink);
+    }
+    pub fn clear_tracer(&mut self) {
+        self.tracer = None;
+    }
+    // event is only built if there's a sink to send it to
+    fn trace<F: FnOnce() -> TraceEvent<W>>(&self, event: F) {
+        if let Some(sink) = &self.tracer {
+            if let Ok(mut sink) = sink.lock() {
+                sink.event(&self.id, &event());
+            }
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, bp: Breakpoint) {
+        self.breakpoints.insert(bp);
+    }
+    pub fn remove_breakpoint(&mut self, bp: Breakpoint) -> bool {
+        self.breakpoints.remove(&bp)
+    }
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+    // like watchpoints, profiles only count the program's own accesses
+    fn profile_read(&self, addr: Word) {
+        if self.executing {
+            self.count_read(addr);
+        }
+    }
+    fn profile_write(&self, addr: Word) {
+        if self.executing {
+            self.count_write(addr);
+        }
     }
-    pub fn adjust_relative_base(&mut self, delta: Word) -> Result<(), String> {
-        self.relative_base += delta;
-        if self.trace {
-            println!(
-                "{}     adjust_relative_base(delta={}) -> rb={}",
-                self.id, delta, self.relative_base
-            );
+    fn watch(&self, bp: Breakpoint) {
+        if self.executing && !self.breakpoints.is_empty() && self.breakpoints.contains(&bp) {
+            self.watch_hit.set(Some(bp));
         }
+    }
+    // A failed instruction is abandoned: its watchpoint hits are dropped, its
+    // journal entry closed, and later writes no longer blamed on it.
+    fn finish_executing(
+        &mut self,
+        res: Result<(), IntcodeError<W>>,
+    ) -> Result<(), IntcodeError<W>> {
+        self.executing = false;
+        if res.is_err() {
+            self.watch_hit.set(None);
+            self.journal_abort();
+            self.smc_end();
+        }
+        res
+    }
+    // pc and opcode breakpoints fire before the instruction executes
+    fn check_breakpoints(&self, inst: &ops::Instruction<W>) -> Option<Breakpoint> {
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+        [
+            Breakpoint::Pc(self.pc),
+            Breakpoint::Opcode(inst.operation.opcode),
+        ]
+        .iter()
+        .find(|bp| self.breakpoints.contains(bp))
+        .copied()
+    }
+
+    fn addr(&self, addr: Word) -> Result<usize, IntcodeError<W>> {
+        if addr < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                pc: self.pc,
+                addr,
+                relative_base: self.relative_base,
+            });
+        }
+        Ok(addr as usize)
+    }
+    // base + offset, for addresses relative to the pc or relative base
+    pub(crate) fn offset(&self, base: Word, offset: Word) -> Result<Word, IntcodeError<W>> {
+        base.checked_add(offset)
+            .ok_or_else(|| IntcodeError::AddressOutOfRange {
+                pc: self.pc,
+                value: W::from_word(offset),
+            })
+    }
+    // a value used as an address, jump target or relative base offset
+    pub(crate) fn as_word(&self, val: W) -> Result<Word, IntcodeError<W>> {
+        match val.to_word() {
+            Some(w) => Ok(w),
+            None => Err(IntcodeError::AddressOutOfRange {
+                pc: self.pc,
+                value: val,
+            }),
+        }
+    }
+    pub fn adjust_relative_base(&mut self, delta: Word) -> Result<(), IntcodeError<W>> {
+        self.relative_base = self.offset(self.relative_base, delta)?;
+        let rb = self.relative_base;
+        self.trace(|| TraceEvent::RelativeBase { delta, rb });
         Ok(())
     }
-    pub fn read_instruction(&self) -> Result<ops::Instruction, String> {
+    pub fn read_instruction(&self) -> Result<ops::Instruction<W>, IntcodeError<W>> {
         self.ops.parse(self)
     }
-    pub fn set_jump(&mut self, addr: Word) -> Result<(), String> {
+    pub fn set_jump(&mut self, addr: Word) -> Result<(), IntcodeError<W>> {
         self.jump = Some(addr);
         Ok(())
     }
-    pub fn halt(&mut self) -> Result<(), String> {
-        if self.trace {
-            println!("{}     halt()", self.id);
-        }
+    pub fn halt(&mut self) -> Result<(), IntcodeError<W>> {
+        self.trace(|| TraceEvent::Halt);
         self.state = Some(RuntimeState::Complete);
         Ok(())
     }
 
-    pub fn read(&mut self, addr: Word) -> Result<(), String> {
+    pub fn read(&mut self, addr: Word) -> Result<(), IntcodeError<W>> {
+        // check now, so a bad address is reported at the input instruction
+        self.addr(addr)?;
         self.read_addr = Some(addr);
         self.state = Some(RuntimeState::Resumable(None));
-        if self.trace {
-            println!("{}     read(addr={})", self.id, addr);
-        }
         Ok(())
     }
-    pub fn write(&mut self, val: Word) -> Result<(), String> {
+    pub fn write(&mut self, val: W) -> Result<(), IntcodeError<W>> {
+        self.trace(|| TraceEvent::Output { val: val.clone() });
         self.state = Some(RuntimeState::Resumable(Some(val)));
-        if self.trace {
-            println!("{}     write(val={})", self.id, val);
-        }
         Ok(())
     }
 
-    pub fn start(&mut self) -> Result<bool, String> {
+    pub fn start(&mut self) -> Result<bool, IntcodeError<W>> {
         match self.resume(None)? {
             RuntimeState::Resumable(None) => Ok(true),
             RuntimeState::Complete => Ok(false),
-            RuntimeState::Resumable(Some(x)) => Err(format!("Unexpected output: {}", x)),
+            RuntimeState::Resumable(Some(x)) => Err(IntcodeError::UnexpectedOutput { value: x }),
+            RuntimeState::Break(bp) => Err(IntcodeError::UnexpectedBreak { breakpoint: bp }),
+            RuntimeState::OutOfFuel => Err(self.out_of_fuel()),
         }
     }
 
-    pub fn resume(&mut self, val: Option<Word>) -> Result<RuntimeState, String> {
-        if self.trace {
-            println!("{} resume({:?})", self.id, val);
-        }
+    pub fn resume(&mut self, val: Option<W>) -> Result<RuntimeState<W>, IntcodeError<W>> {
         if let Some(RuntimeState::Complete) = self.state {
-            return Err("Cannot resume, program complete".to_string());
+            return Err(IntcodeError::Halted);
         }
         if let Some(addr) = self.read_addr {
             match val {
-                Some(x) => self.set(addr, x)?,
-                None => return Err("Expected to resume with a value and did not".to_string()),
+                Some(x) => {
+                    self.trace(|| TraceEvent::Input {
+                        addr,
+                        val: x.clone(),
+                    });
+                    self.journal_input_begin();
+                    self.executing = true;
+                    let res = self.set(addr, x.clone());
+                    self.finish_executing(res)?;
+                    self.journal_input_end(x);
+                }
+                None => return Err(IntcodeError::MissingInput { pc: self.pc }),
             }
         }
         self.read_addr = None;
+        self.smc_end();
         self.state = None;
 
-        while let None = self.state {
-            if self.trace {
-                println!("\n{} pc={} rb={}", self.id, self.pc, self.relative_base);
-            }
-            if self.trace_state {
-                self.print_state()
-            }
+        // watchpoint on the input address, or one left over from an output
+        if let Some(bp) = self.watch_hit.take() {
+            self.state = Some(RuntimeState::Break(bp));
+            return Ok(RuntimeState::Break(bp));
+        }
+        let mut skip_break = self.skip_break;
+        self.skip_break = false;
+        // checked once, so instrumentation enabled by an instruction takes
+        // effect from the next resume
+        let instrumented = self.instrumented();
+
+        while self.state.is_none() {
             // if self.pc >= self.mem.len() {
             //     return Err("Reached end of program".to_string());
             // }
+            if !instrumented {
+                let inst = self.read_instruction()?;
+                inst.execute_builtin(self)?;
+                self.executed += 1;
+                self.advance(&inst)?;
+                continue;
+            }
+            if self.budget_exhausted() {
+                // still step over the breakpoint we stopped at, once refuelled
+                self.skip_break = skip_break;
+                self.state = Some(RuntimeState::OutOfFuel);
+                break;
+            }
 
             let inst = self.read_instruction()?;
-            inst.execute(self)?;
-            match self.jump {
-                None => {
-                    self.pc += inst.operation.params as Word + 1;
+            if !skip_break {
+                if let Some(bp) = self.check_breakpoints(&inst) {
+                    self.skip_break = true;
+                    self.state = Some(RuntimeState::Break(bp));
+                    break;
                 }
-                Some(addr) => {
-                    self.pc = addr;
-                    self.jump = None;
+            }
+            skip_break = false;
+
+            let pc = self.pc;
+            self.trace(|| TraceEvent::Instruction {
+                pc,
+                instruction: inst.clone(),
+            });
+            self.journal_begin();
+            self.smc_begin(pc, inst.size());
+            self.executing = true;
+            let res = inst.execute(self);
+            self.finish_executing(res)?;
+            self.budget_spend();
+            self.profile_instruction(pc, &inst, self.jump);
+            if let Some(c) = &mut self.coverage {
+                c.add(pc);
+            }
+            self.journal_end();
+            self.smc_end();
+            self.advance(&inst)?;
+
+            // watchpoints fire after the accessing instruction completes. if it
+            // also produced output or halted, that wins and the break waits
+            // for the next resume (or is dropped, once complete)
+            if let Some(bp) = self.watch_hit.take() {
+                match self.state {
+                    None => self.state = Some(RuntimeState::Break(bp)),
+                    Some(RuntimeState::Complete) => (),
+                    Some(_) => self.watch_hit.set(Some(bp)),
                 }
             }
+
+            if self.single_step && self.state.is_none() {
+                self.skip_break = true;
+                self.state = Some(RuntimeState::Break(Breakpoint::Step));
+            }
+        }
+        Ok(self.state.clone().unwrap())
+    }
+
+    // Whether anything needs to see each instruction. If not, resume skips
+    // all the per-instruction hooks, custom actions included.
+    fn instrumented(&self) -> bool {
+        self.tracer.is_some()
+            || self.journal.is_some()
+            || self.smc.is_some()
+            || self.profile.is_some()
+            || self.coverage.is_some()
+            || !self.breakpoints.is_empty()
+            || self.single_step
+            || self.budget_limited()
+            || self.ops.has_actions()
+    }
+    // move past inst, or to where it jumped
+    fn advance(&mut self, inst: &ops::Instruction<W>) -> Result<(), IntcodeError<W>> {
+        match self.jump.take() {
+            None => match self.offset(self.pc, inst.size() as Word) {
+                Ok(next) => self.pc = next,
+                // a halt at the very top of memory has nowhere to go, but
+                // doesn't need to
+                Err(_) if matches!(self.state, Some(RuntimeState::Complete)) => (),
+                Err(e) => return Err(e),
+            },
+            Some(addr) => self.pc = addr,
         }
-        Ok(self.state.unwrap())
+        Ok(())
+    }
+
+    // like resume, but execute at most one instruction. Returns Break(Step) if
+    // that instruction didn't otherwise stop the program. Breakpoints at the
+    // current pc are ignored, and so is one at the pc stepped to if execution
+    // is continued from there.
+    pub fn step_instruction(&mut self, val: Option<W>) -> Result<RuntimeState<W>, IntcodeError<W>> {
+        self.single_step = true;
+        self.skip_break = true;
+        let res = self.resume(val);
+        self.single_step = false;
+        res
     }
-    fn print_state(&mut self) {
+
+    // memory from start to end (exclusive), with the current instruction
+    // underlined if it's in range
+    pub fn format_state(&self, start: Word, end: Word) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
         let mut x = 0;
         let mut addr_pos = HashMap::new();
         // 0   1 2   3  4    5   6 7   8    9   10 11  12   13  14 15
@@ -234,105 +578,112 @@ impl Runtime {
         //              ^----i---p-p--
 
         // line 1: addresses
-        let max_addr = self.mem.keys().max().unwrap();
+        let start = start.max(0);
         let padding = 2;
-        print!("\n");
-        for addr in 0..=*max_addr {
+        for addr in start..end {
             let addr_s = format!("{}", addr);
-            let val_s = format!("{}", self.mem.get(&addr).unwrap_or(&0));
+            let val_s = format!("{}", self.mem.get(addr as usize));
             let width = addr_s.len().max(val_s.len()) + padding;
             addr_pos.insert(addr, (x, width));
             x += width;
-            print!("{:<1$}", addr_s, width);
+            write!(out, "{:<1$}", addr_s, width).unwrap();
         }
-        print!("\n");
+        out.push('\n');
 
         // line 2: values
-        for addr in 0..=*max_addr {
+        for addr in start..end {
             let (_x, width) = addr_pos.get(&addr).unwrap_or(&(0, 1));
-            print!("{:<1$}", self.mem.get(&addr).unwrap_or(&0), width);
+            write!(out, "{:<1$}", self.mem.get(addr as usize), width).unwrap();
         }
-        print!("\n");
+        out.push('\n');
 
         // line 3: current instruction
-        let pc = addr_pos.get(&self.pc).unwrap();
-        let inst = self.read_instruction().unwrap();
-        let inst_width = (0..inst.operation.params)
-            .map(|i| addr_pos.get(&(self.pc + i as i64 + 1)).unwrap_or(&(0, 1)).1)
-            .sum::<usize>();
-        print!(
-            "{}^{}\n{:?}\n",
-            " ".repeat(pc.0),
-            "-".repeat(pc.1 + inst_width - 1),
-            inst,
-        );
+        if let (Some(pc), Ok(inst)) = (addr_pos.get(&self.pc), self.read_instruction()) {
+            let inst_width = (0..inst.operation.params)
+                .map(|i| addr_pos.get(&(self.pc + i as i64 + 1)).unwrap_or(&(0, 1)).1)
+                .sum::<usize>();
+            writeln!(
+                out,
+                "{}^{}\n{:?}",
+                " ".repeat(pc.0),
+                "-".repeat(pc.1 + inst_width - 1),
+                inst,
+            )
+            .unwrap();
+        }
+        out
     }
 
     // helper for passing an input, retrieving an output
     // (output, done)
-    pub fn step(&mut self, val: Word) -> Result<(Word, bool), String> {
-        let r = self.stepn(vec![val], 1)?;
+    pub fn step(&mut self, val: W) -> Result<(W, bool), IntcodeError<W>> {
+        let r = self.stepn(vec![val], 1)?.remove(0);
         // advance one more time and return
         match self.resume(None)? {
-            RuntimeState::Complete => Ok((r[0], true)),
-            RuntimeState::Resumable(None) => Ok((r[0], false)),
-            RuntimeState::Resumable(Some(_)) => {
-                Err("Unexpected output after reading all outputs".to_string())
+            RuntimeState::Complete => Ok((r, true)),
+            RuntimeState::Resumable(None) => Ok((r, false)),
+            RuntimeState::Resumable(Some(x)) => {
+                Err(IntcodeError::UnexpectedOutput { value: x }).wrap("after reading all outputs")
             }
+            RuntimeState::Break(bp) => Err(IntcodeError::UnexpectedBreak { breakpoint: bp }),
+            RuntimeState::OutOfFuel => Err(self.out_of_fuel()),
         }
     }
 
     // helper for passing any number of inputs, then getting n outputs
-    pub fn stepn(&mut self, vals: Vec<Word>, n: usize) -> Result<Vec<Word>, String> {
+    pub fn stepn(&mut self, mut vals: Vec<W>, n: usize) -> Result<Vec<W>, IntcodeError<W>> {
         // provide all but one input
-        if vals.len() > 0 {
-            for v in &vals[0..vals.len() - 1] {
-                match self.resume(Some(*v))? {
-                    RuntimeState::Complete => {
-                        return Err("Unexpected complete during inputs".to_string())
-                    }
-                    RuntimeState::Resumable(Some(_)) => {
-                        return Err("Unexpected output during inputs".to_string())
-                    }
-                    RuntimeState::Resumable(None) => (),
+        let mut val = vals.pop();
+        for v in vals {
+            match self.resume(Some(v))? {
+                RuntimeState::Complete => {
+                    return Err(IntcodeError::UnexpectedHalt).wrap("during inputs")
+                }
+                RuntimeState::Resumable(Some(x)) => {
+                    return Err(IntcodeError::UnexpectedOutput { value: x }).wrap("during inputs")
+                }
+                RuntimeState::Resumable(None) => (),
+                RuntimeState::Break(bp) => {
+                    return Err(IntcodeError::UnexpectedBreak { breakpoint: bp })
                 }
+                RuntimeState::OutOfFuel => return Err(self.out_of_fuel()),
             }
         }
 
         // provide the last input, read n outputs
         let mut out = Vec::new();
-        let mut val = if vals.len() > 0 {
-            Some(vals[vals.len() - 1])
-        } else {
-            None
-        };
         for i in 0..n {
-            match self.resume(val)? {
+            match self.resume(val.take())? {
                 RuntimeState::Complete => {
-                    return Err(format!("Unexpected complete after output {}", i));
+                    return Err(IntcodeError::UnexpectedHalt).wrap(format!("after output {}", i));
                 }
                 RuntimeState::Resumable(None) => {
-                    return Err(format!("Unexpected ask for input after output {}", i));
+                    return Err(IntcodeError::UnexpectedInput).wrap(format!("after output {}", i));
                 }
-                RuntimeState::Resumable(Some(x)) => {
-                    val = None;
-                    out.push(x);
+                RuntimeState::Resumable(Some(x)) => out.push(x),
+                RuntimeState::Break(bp) => {
+                    return Err(IntcodeError::UnexpectedBreak { breakpoint: bp })
                 }
+                RuntimeState::OutOfFuel => return Err(self.out_of_fuel()),
             }
         }
 
         Ok(out)
     }
 
-    pub fn step_read(&mut self, n: usize) -> Result<Option<Vec<Word>>, String> {
-        let mut out = vec![0; n];
+    pub fn step_read(&mut self, n: usize) -> Result<Option<Vec<W>>, IntcodeError<W>> {
+        let mut out = Vec::with_capacity(n);
         for i in 0..n {
             match self.resume(None)? {
                 RuntimeState::Complete => return Ok(None),
                 RuntimeState::Resumable(None) => {
-                    return Err(format!("Unexpected ask for input at output {}", i))
+                    return Err(IntcodeError::UnexpectedInput).wrap(format!("at output {}", i))
+                }
+                RuntimeState::Resumable(Some(x)) => out.push(x),
+                RuntimeState::Break(bp) => {
+                    return Err(IntcodeError::UnexpectedBreak { breakpoint: bp })
                 }
-                RuntimeState::Resumable(Some(x)) => out[i] = x,
+                RuntimeState::OutOfFuel => return Err(self.out_of_fuel()),
             }
         }
         Ok(Some(out))
@@ -340,32 +691,66 @@ impl Runtime {
 
     pub fn run_io(
         &mut self,
-        input: &mut dyn io::Input,
-        output: &mut dyn io::Output,
-    ) -> Result<(), String> {
+        input: &mut dyn io::Input<W>,
+        output: &mut dyn io::Output<W>,
+    ) -> Result<(), IntcodeError<W>> {
         let mut next = None;
-        while let RuntimeState::Resumable(val) = self.resume(next)? {
-            match val {
+        loop {
+            match self.resume(next)? {
                 // output, expect nothing back
-                Some(x) => {
+                RuntimeState::Resumable(Some(x)) => {
                     output.write(x)?;
                     next = None;
                 }
                 // input, expect a new value
-                None => {
+                RuntimeState::Resumable(None) => {
                     next = Some(input.read()?);
                 }
+                RuntimeState::Break(bp) => {
+                    return Err(IntcodeError::UnexpectedBreak { breakpoint: bp })
+                }
+                RuntimeState::OutOfFuel => return Err(self.out_of_fuel()),
+                RuntimeState::Complete => return Ok(()),
             }
         }
-        Ok(())
     }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
-pub enum RuntimeState {
+pub enum RuntimeState<W = Word> {
     // Resumable(Some(_)) broken to output some value, nothing expected back
     // Resumable(None) broken to collect some input
-    Resumable(Option<Word>),
+    Resumable(Option<W>),
+    // stopped at a breakpoint or watchpoint, resume(None) to carry on
+    Break(Breakpoint),
     // halted
     Complete,
+    // the fuel or deadline set on the runtime ran out before the next
+    // instruction; add more and resume(None) to carry on
+    OutOfFuel,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Breakpoint {
+    // before executing the instruction at this address
+    Pc(Word),
+    // after an instruction reads or writes this address
+    Read(Word),
+    Write(Word),
+    // before executing any instruction with this opcode
+    Opcode(u8),
+    // after each instruction run by step_instruction; never registered
+    Step,
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Pc(addr) => write!(f, "pc={}", addr),
+            Breakpoint::Read(addr) => write!(f, "read of [{}]", addr),
+            Breakpoint::Write(addr) => write!(f, "write to [{}]", addr),
+            Breakpoint::Opcode(op) => write!(f, "opcode {}", op),
+            Breakpoint::Step => write!(f, "step"),
+        }
+    }
 }
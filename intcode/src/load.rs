@@ -0,0 +1,69 @@
+use super::{Int, IntcodeError, Program};
+use std::io::Read;
+use std::path::Path;
+
+// Program text is words separated by commas, whitespace or both, so a
+// program can be split over lines. `#` starts a comment running to the end
+// of the line, and a trailing comma is fine, but an empty word between two
+// commas is an error, since dropping it would move everything after it.
+//
+//   # day 2 example
+//   1,9,10,3,
+//   2,3,11,0,
+//   99,          # halt
+//   30,40,50
+
+impl Program {
+    // for other Int types, read the file and use str::parse
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, IntcodeError> {
+        let file = std::fs::File::open(path).map_err(|e| IntcodeError::Io(e.to_string()))?;
+        Program::from_reader(file)
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, IntcodeError> {
+        let mut src = String::new();
+        reader
+            .read_to_string(&mut src)
+            .map_err(|e| IntcodeError::Io(e.to_string()))?;
+        src.parse()
+    }
+}
+
+pub(crate) fn parse_words<W: Int>(src: &str) -> Result<Vec<W>, IntcodeError<W>> {
+    let mut words = Vec::new();
+    // a comma since the last word, or at the start, so another comma means
+    // an empty word
+    let mut need_word = true;
+    let mut chars = src.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '#' => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+            ',' if need_word => {
+                // "" never parses, so this is the error for an empty word
+                words.push(word(words.len(), offset, "")?);
+            }
+            ',' => need_word = true,
+            c if c.is_whitespace() => (),
+            _ => {
+                let mut end = offset + c.len_utf8();
+                while let Some((i, c)) =
+                    chars.next_if(|&(_, c)| c != ',' && c != '#' && !c.is_whitespace())
+                {
+                    end = i + c.len_utf8();
+                }
+                words.push(word(words.len(), offset, &src[offset..end])?);
+                need_word = false;
+            }
+        }
+    }
+    Ok(words)
+}
+
+fn word<W: Int>(index: usize, offset: usize, token: &str) -> Result<W, IntcodeError<W>> {
+    W::parse(token).map_err(|source| IntcodeError::Parse {
+        index,
+        offset,
+        token: token.to_string(),
+        source,
+    })
+}
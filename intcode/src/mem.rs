@@ -0,0 +1,55 @@
+use super::{Int, Word};
+use std::collections::HashMap;
+
+// addresses at or above this live in the sparse map instead of growing the Vec
+const DENSE_LIMIT: usize = 1 << 20;
+
+// Runtime memory: a flat Vec that grows on write, with a HashMap fallback for
+// very large addresses. Anything never written reads as 0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Memory<W = Word> {
+    dense: Vec<W>,
+    sparse: HashMap<usize, W>,
+}
+
+impl<W: Int> Memory<W> {
+    pub fn new(initial: Vec<W>) -> Self {
+        Memory {
+            dense: initial,
+            sparse: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, addr: usize) -> W {
+        match self.dense.get(addr) {
+            Some(val) => val.clone(),
+            None if addr < DENSE_LIMIT => W::from_word(0),
+            None => match self.sparse.get(&addr) {
+                Some(val) => val.clone(),
+                None => W::from_word(0),
+            },
+        }
+    }
+
+    pub fn set(&mut self, addr: usize, val: W) {
+        if addr < self.dense.len() {
+            self.dense[addr] = val;
+        } else if addr < DENSE_LIMIT {
+            self.dense.resize(addr + 1, W::from_word(0));
+            self.dense[addr] = val;
+        } else {
+            self.sparse.insert(addr, val);
+        }
+    }
+
+    pub fn dense(&self) -> &[W] {
+        &self.dense
+    }
+
+    // sparse (very high) addresses in ascending order
+    pub fn sparse(&self) -> Vec<(usize, W)> {
+        let mut v: Vec<_> = self.sparse.iter().map(|(a, w)| (*a, w.clone())).collect();
+        v.sort_unstable_by_key(|(a, _)| *a);
+        v
+    }
+}
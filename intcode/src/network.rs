@@ -0,0 +1,220 @@
+use super::error::ErrWrapExt;
+use super::{Int, IntcodeError, Program, Runtime, RuntimeState, Word};
+use std::collections::VecDeque;
+
+pub type NodeId = usize;
+
+// A set of machines whose outputs feed each other's inputs, run on one thread
+// round-robin: each node in turn runs until it wants input it doesn't have
+// (or halts), in the order the nodes were added, so runs are repeatable.
+//
+//   let mut net = Network::new();
+//   let a = net.add_node("a", &prog, vec![phase_a, 0]);
+//   let b = net.add_node("b", &prog, vec![phase_b]);
+//   net.connect(a, b);
+//   net.connect(b, a);
+//   net.run()?;
+//   net.outputs(b).last()
+pub struct Network<W = Word> {
+    nodes: Vec<Node<W>>,
+    unrouted: Vec<(NodeId, Vec<W>)>,
+}
+
+struct Node<W> {
+    name: String,
+    rt: Runtime<W>,
+    inbox: VecDeque<W>,
+    routes: Vec<NodeId>,
+    // for packet routing: words per packet, and the packet so far
+    packet_size: Option<usize>,
+    packet: Vec<W>,
+    // given instead of blocking when the inbox is empty, at most once per turn
+    idle_input: Option<W>,
+    outputs: Vec<W>,
+    waiting: bool,
+    halted: bool,
+}
+
+impl<W: Int> Default for Network<W> {
+    fn default() -> Self {
+        Network {
+            nodes: Vec::new(),
+            unrouted: Vec::new(),
+        }
+    }
+}
+
+impl<W: Int> Network<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // inputs are queued for the node before anything else it receives
+    pub fn add_node(&mut self, name: &str, prog: &Program<W>, inputs: Vec<W>) -> NodeId {
+        let mut rt = prog.new_runtime();
+        rt.id = name.to_string();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            rt,
+            inbox: inputs.into(),
+            routes: Vec::new(),
+            packet_size: None,
+            packet: Vec::new(),
+            idle_input: None,
+            outputs: Vec::new(),
+            waiting: false,
+            halted: false,
+        });
+        self.nodes.len() - 1
+    }
+
+    // every output of from is also sent to to; connect to several nodes to fan out
+    pub fn connect(&mut self, from: NodeId, to: NodeId) {
+        self.nodes[from].routes.push(to);
+    }
+
+    // Treat from's outputs as packets of size words: a destination node id
+    // followed by size-1 words of payload, which are queued for that node.
+    // Packets for ids that aren't nodes end up in unrouted().
+    pub fn route_packets(&mut self, from: NodeId, size: usize) {
+        assert!(size > 0, "packets need at least an address");
+        self.nodes[from].packet_size = Some(size);
+    }
+
+    // give the node val instead of blocking when it has no input (e.g. -1
+    // for "no packet")
+    pub fn set_idle_input(&mut self, node: NodeId, val: Option<W>) {
+        self.nodes[node].idle_input = val;
+    }
+
+    // queue a value for a node from outside the network
+    pub fn send(&mut self, node: NodeId, val: W) {
+        self.nodes[node].inbox.push_back(val);
+    }
+
+    // everything the node has output so far, routed or not
+    pub fn outputs(&self, node: NodeId) -> &[W] {
+        &self.nodes[node].outputs
+    }
+    // (sender, packet) for packets addressed to a node that doesn't exist
+    pub fn unrouted(&self) -> &[(NodeId, Vec<W>)] {
+        &self.unrouted
+    }
+    pub fn runtime(&self, node: NodeId) -> &Runtime<W> {
+        &self.nodes[node].rt
+    }
+    pub fn is_halted(&self, node: NodeId) -> bool {
+        self.nodes[node].halted
+    }
+
+    // Run until every node halts. If a whole round passes where no node
+    // halts, outputs anything or takes any (non-idle) input, nothing else
+    // can happen, and that's an IntcodeError::Deadlock naming the nodes
+    // still waiting.
+    pub fn run(&mut self) -> Result<(), IntcodeError<W>> {
+        self.run_until(|_| false).map(|_| ())
+    }
+
+    // like run, but stop early (returning true) once done(self) is true; it's
+    // checked after every node's turn
+    pub fn run_until<F>(&mut self, mut done: F) -> Result<bool, IntcodeError<W>>
+    where
+        F: FnMut(&Network<W>) -> bool,
+    {
+        loop {
+            if self.nodes.iter().all(|n| n.halted) {
+                return Ok(false);
+            }
+            let mut progress = false;
+            for i in 0..self.nodes.len() {
+                progress |= self.turn(i)?;
+                if done(self) {
+                    return Ok(true);
+                }
+            }
+            if !progress {
+                let waiting = self
+                    .nodes
+                    .iter()
+                    .filter(|n| !n.halted)
+                    .map(|n| n.name.clone())
+                    .collect();
+                return Err(IntcodeError::Deadlock { waiting });
+            }
+        }
+    }
+
+    // run node i until it blocks or halts; returns whether anything happened
+    fn turn(&mut self, i: NodeId) -> Result<bool, IntcodeError<W>> {
+        let mut progress = false;
+        let mut idle_used = false;
+        let mut sent = Vec::new();
+        let node = &mut self.nodes[i];
+        while !node.halted {
+            let val = if node.waiting {
+                match node.inbox.pop_front() {
+                    Some(v) => {
+                        progress = true;
+                        Some(v)
+                    }
+                    None => match &node.idle_input {
+                        Some(v) if !idle_used => {
+                            idle_used = true;
+                            Some(v.clone())
+                        }
+                        _ => break,
+                    },
+                }
+            } else {
+                None
+            };
+            node.waiting = false;
+            match node.rt.resume(val).wrap(format!("node {}", node.name))? {
+                RuntimeState::Resumable(None) => node.waiting = true,
+                RuntimeState::Resumable(Some(x)) => {
+                    progress = true;
+                    node.outputs.push(x.clone());
+                    sent.push(x);
+                }
+                RuntimeState::Complete => {
+                    progress = true;
+                    node.halted = true;
+                }
+                RuntimeState::Break(bp) => {
+                    return Err(IntcodeError::UnexpectedBreak { breakpoint: bp })
+                        .wrap(format!("node {}", node.name))
+                }
+                RuntimeState::OutOfFuel => {
+                    return Err(node.rt.out_of_fuel()).wrap(format!("node {}", node.name))
+                }
+            }
+        }
+        for x in sent {
+            self.deliver(i, x);
+        }
+        Ok(progress)
+    }
+
+    fn deliver(&mut self, from: NodeId, val: W) {
+        let node = &mut self.nodes[from];
+        let size = match node.packet_size {
+            None => {
+                for to in node.routes.clone() {
+                    self.nodes[to].inbox.push_back(val.clone());
+                }
+                return;
+            }
+            Some(size) => size,
+        };
+        node.packet.push(val);
+        if node.packet.len() < size {
+            return;
+        }
+        let packet = std::mem::take(&mut node.packet);
+        let to = packet[0].to_word().filter(|&a| a >= 0);
+        match to.and_then(|a| self.nodes.get_mut(a as usize)) {
+            Some(to) => to.inbox.extend(packet[1..].iter().cloned()),
+            _ => self.unrouted.push((from, packet)),
+        }
+    }
+}
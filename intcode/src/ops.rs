@@ -1,180 +1,336 @@
-use super::{Runtime, Word};
-use std::collections::HashMap;
-
-type Opcode = u8;
-
-pub const OP_ADD: Operation = Operation {
-    opcode: 1,
-    name: "add",
-    params: 3,
-    action: &ClosureAction(|rt, params| {
-        let lhs = params[0].resolve(rt)?;
-        let rhs = params[1].resolve(rt)?;
-        let addr = params[2].position(rt).wrap("add")?;
-        let val = lhs + rhs;
-        if rt.trace {
-            println!(
-                "{}   setting addr {} = {} = {} + {}",
-                rt.id, addr, val, lhs, rhs
-            );
-        }
-        rt.set(addr, val)
-    }),
-};
-
-pub const OP_MUL: Operation = Operation {
-    opcode: 2,
-    name: "multiply",
-    params: 3,
-    action: &ClosureAction(|rt, params| {
-        let lhs = params[0].resolve(rt)?;
-        let rhs = params[1].resolve(rt)?;
-        let addr = params[2].position(rt).wrap("multiply")?;
-        let val = lhs * rhs;
-        if rt.trace {
-            println!(
-                "{}   setting addr {} = {} = {} * {}",
-                rt.id, addr, val, lhs, rhs
-            );
-        }
-        rt.set(addr, val)
-    }),
-};
-
-pub const OP_INP: Operation = Operation {
-    opcode: 3,
-    name: "input",
-    params: 1,
-    action: &ClosureAction(|rt, params| rt.read(params[0].position(rt).wrap("input")?)),
-};
-
-pub const OP_OUT: Operation = Operation {
-    opcode: 4,
-    name: "output",
-    params: 1,
-    action: &ClosureAction(|rt, params| rt.write(params[0].resolve(rt)?)),
-};
-
-pub const OP_JIT: Operation = Operation {
-    opcode: 5,
-    name: "jump-if-true",
-    params: 2,
-    action: &ClosureAction(|rt, params| {
-        let pred = params[0].resolve(rt)?;
-        if pred != 0 {
-            rt.set_jump(params[1].resolve(rt)?)?;
-        }
-        Ok(())
-    }),
-};
-
-pub const OP_JIF: Operation = Operation {
-    opcode: 6,
-    name: "jump-if-false",
-    params: 2,
-    action: &ClosureAction(|rt, params| {
-        let pred = params[0].resolve(rt)?;
-        if pred == 0 {
-            rt.set_jump(params[1].resolve(rt)?)?;
-        }
-        Ok(())
-    }),
-};
-
-pub const OP_LT: Operation = Operation {
-    opcode: 7,
-    name: "less-than",
-    params: 3,
-    action: &ClosureAction(|rt, params| {
-        let lhs = params[0].resolve(rt)?;
-        let rhs = params[1].resolve(rt)?;
-        let addr = params[2].position(rt).wrap("less-than")?;
-        let val = if lhs < rhs { 1 } else { 0 };
-        if rt.trace {
-            println!(
-                "{}   setting addr {} = {} = ({} < {})",
-                rt.id, addr, val, lhs, rhs
-            );
-        }
-        rt.set(addr, val)
-    }),
-};
-
-pub const OP_EQ: Operation = Operation {
-    opcode: 8,
-    name: "equals",
-    params: 3,
-    action: &ClosureAction(|rt, params| {
-        let lhs = params[0].resolve(rt)?;
-        let rhs = params[1].resolve(rt)?;
-        let addr = params[2].position(rt).wrap("equals")?;
-        let val = if lhs == rhs { 1 } else { 0 };
-        if rt.trace {
-            println!(
-                "{}   setting addr {} = {} = ({} == {})",
-                rt.id, addr, val, lhs, rhs
-            );
-        }
-        rt.set(addr, val)
-    }),
-};
+use super::error::ErrWrapExt;
+use super::{Int, IntcodeError, Runtime, Word};
+use std::sync::Arc;
+
+// no built-in operation takes more than this many params
+pub const MAX_PARAMS: usize = 3;
+
+impl<W: Int> Operation<W> {
+    pub const ADD: Self = Operation {
+        opcode: 1,
+        name: "add",
+        params: 3,
+        action: |rt, params| {
+            let lhs = params[0].resolve(rt)?;
+            let rhs = params[1].resolve(rt)?;
+            let addr = params[2].position(rt, 2).wrap("add")?;
+            match lhs.add(&rhs, rt.arithmetic()) {
+                Some(val) => rt.set(addr, val),
+                None => Err(overflow(rt, lhs, rhs)),
+            }
+        },
+    };
+
+    pub const MUL: Self = Operation {
+        opcode: 2,
+        name: "multiply",
+        params: 3,
+        action: |rt, params| {
+            let lhs = params[0].resolve(rt)?;
+            let rhs = params[1].resolve(rt)?;
+            let addr = params[2].position(rt, 2).wrap("multiply")?;
+            match lhs.mul(&rhs, rt.arithmetic()) {
+                Some(val) => rt.set(addr, val),
+                None => Err(overflow(rt, lhs, rhs)),
+            }
+        },
+    };
+
+    pub const INP: Self = Operation {
+        opcode: 3,
+        name: "input",
+        params: 1,
+        action: |rt, params| rt.read(params[0].position(rt, 0).wrap("input")?),
+    };
+
+    pub const OUT: Self = Operation {
+        opcode: 4,
+        name: "output",
+        params: 1,
+        action: |rt, params| rt.write(params[0].resolve(rt)?),
+    };
+
+    pub const JIT: Self = Operation {
+        opcode: 5,
+        name: "jump-if-true",
+        params: 2,
+        action: |rt, params| {
+            let pred = params[0].resolve(rt)?;
+            if pred != W::from_word(0) {
+                let to = params[1].resolve(rt)?;
+                rt.set_jump(rt.as_word(to)?)?;
+            }
+            Ok(())
+        },
+    };
+
+    pub const JIF: Self = Operation {
+        opcode: 6,
+        name: "jump-if-false",
+        params: 2,
+        action: |rt, params| {
+            let pred = params[0].resolve(rt)?;
+            if pred == W::from_word(0) {
+                let to = params[1].resolve(rt)?;
+                rt.set_jump(rt.as_word(to)?)?;
+            }
+            Ok(())
+        },
+    };
+
+    pub const LT: Self = Operation {
+        opcode: 7,
+        name: "less-than",
+        params: 3,
+        action: |rt, params| {
+            let lhs = params[0].resolve(rt)?;
+            let rhs = params[1].resolve(rt)?;
+            let addr = params[2].position(rt, 2).wrap("less-than")?;
+            let val = if lhs < rhs { 1 } else { 0 };
+            rt.set(addr, W::from_word(val))
+        },
+    };
+
+    pub const EQ: Self = Operation {
+        opcode: 8,
+        name: "equals",
+        params: 3,
+        action: |rt, params| {
+            let lhs = params[0].resolve(rt)?;
+            let rhs = params[1].resolve(rt)?;
+            let addr = params[2].position(rt, 2).wrap("equals")?;
+            let val = if lhs == rhs { 1 } else { 0 };
+            rt.set(addr, W::from_word(val))
+        },
+    };
 
-pub const OP_RBO: Operation = Operation {
-    opcode: 9,
-    name: "relative-base-offset",
-    params: 1,
-    action: &ClosureAction(|rt, params| rt.adjust_relative_base(params[0].resolve(rt)?)),
-};
+    pub const RBO: Self = Operation {
+        opcode: 9,
+        name: "relative-base-offset",
+        params: 1,
+        action: |rt, params| {
+            let delta = params[0].resolve(rt)?;
+            rt.adjust_relative_base(rt.as_word(delta)?)
+        },
+    };
 
-pub const OP_HLT: Operation = Operation {
-    opcode: 99,
-    name: "halt",
-    params: 0,
-    action: &ClosureAction(|rt, _params| rt.halt()),
-};
+    pub const HLT: Self = Operation {
+        opcode: 99,
+        name: "halt",
+        params: 0,
+        action: |rt, _params| rt.halt(),
+    };
 
-struct ClosureAction<F: Fn(&mut Runtime, &Vec<Param>) -> Result<(), String>>(F);
-impl<F: Fn(&mut Runtime, &Vec<Param>) -> Result<(), String>> OpAction for ClosureAction<F> {
-    fn execute(&self, rt: &mut Runtime, params: &Vec<Param>) -> Result<(), String> {
-        (self.0)(rt, params)
+    pub const BUILTINS: [Self; 10] = [
+        Self::ADD,
+        Self::MUL,
+        Self::INP,
+        Self::OUT,
+        Self::JIT,
+        Self::JIF,
+        Self::LT,
+        Self::EQ,
+        Self::RBO,
+        Self::HLT,
+    ];
+}
+
+// the built-in operations for the default Word type; generic code uses the
+// associated consts, e.g. Operation::<W>::ADD
+pub const BUILTINS: [Operation; 10] = Operation::BUILTINS;
+pub const OP_ADD: Operation = Operation::ADD;
+pub const OP_MUL: Operation = Operation::MUL;
+pub const OP_INP: Operation = Operation::INP;
+pub const OP_OUT: Operation = Operation::OUT;
+pub const OP_JIT: Operation = Operation::JIT;
+pub const OP_JIF: Operation = Operation::JIF;
+pub const OP_LT: Operation = Operation::LT;
+pub const OP_EQ: Operation = Operation::EQ;
+pub const OP_RBO: Operation = Operation::RBO;
+pub const OP_HLT: Operation = Operation::HLT;
+
+fn overflow<W: Int>(rt: &Runtime<W>, lhs: W, rhs: W) -> IntcodeError<W> {
+    IntcodeError::Overflow {
+        pc: rt.pc,
+        opcode: rt.get_word(0).map_or(0, |w| w.low_digits(2)),
+        lhs,
+        rhs,
+    }
+}
+
+// an operation from register_action run on a runtime without that action
+fn unregistered<W: Int>(rt: &Runtime<W>) -> IntcodeError<W> {
+    match rt.get_word(0) {
+        Ok(word) => IntcodeError::UnknownOpcode {
+            pc: rt.pc,
+            opcode: word.low_digits(2),
+            word,
+        },
+        Err(e) => e,
     }
 }
 
 // Types
 
-pub struct Operations {
-    ops: HashMap<Opcode, &'static Operation>,
+// What add and multiply do when the result doesn't fit in the machine's
+// value type. The same in debug and release builds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Arithmetic {
+    // fail the instruction with IntcodeError::Overflow
+    #[default]
+    Checked,
+    // two's complement wraparound
+    Wrapping,
+    // clamp to the type's minimum or maximum
+    Saturating,
+}
+
+// Opcode lookup table, indexed by the low two digits of the instruction word.
+// Starts with the built-ins; register adds or replaces operations, e.g. to
+// try out an instruction-set extension or an instrumented multiply:
+//
+//   let mut ops = Operations::new();
+//   ops.register_action(2, "multiply", 3, |rt: &mut Runtime, params: &[Param]| {
+//       count_multiplies();
+//       (OP_MUL.action)(rt, params)
+//   });
+//   rt.set_operations(ops);
+#[derive(Clone)]
+pub struct Operations<W = Word> {
+    table: [Option<Operation<W>>; 100],
+    // actions registered with register_action, by opcode; Operation can only
+    // hold a fn, so these run in place of its action
+    actions: Vec<Option<SharedAction<W>>>,
 }
-impl Operations {
+type SharedAction<W> = Arc<dyn OpAction<W> + Send + Sync>;
+
+impl<W: Int> Operations<W> {
     pub fn new() -> Self {
-        let mut ops = HashMap::new();
-        for op in vec![
-            &OP_ADD, &OP_MUL, &OP_INP, &OP_OUT, &OP_HLT, &OP_JIT, &OP_JIF, &OP_EQ, &OP_LT, &OP_RBO,
-        ] {
-            ops.insert(op.opcode, op);
+        let mut table = [None; 100];
+        for op in Operation::BUILTINS.iter() {
+            table[op.opcode as usize] = Some(*op);
+        }
+        Operations {
+            table,
+            actions: Vec::new(),
+        }
+    }
+    // Add op, or replace whatever had its opcode, returning that. Panics if
+    // the opcode isn't below 100 or op takes more than MAX_PARAMS params.
+    pub fn register(&mut self, op: Operation<W>) -> Option<Operation<W>> {
+        assert!(op.opcode < 100, "opcode {} is over two digits", op.opcode);
+        assert!(
+            op.params as usize <= MAX_PARAMS,
+            "{} takes {} params, at most {} are supported",
+            op.name,
+            op.params,
+            MAX_PARAMS
+        );
+        if let Some(action) = self.actions.get_mut(op.opcode as usize) {
+            *action = None;
+        }
+        self.table[op.opcode as usize].replace(op)
+    }
+    // like register, but the action can be anything implementing OpAction,
+    // such as a closure with state of its own
+    pub fn register_action<A>(
+        &mut self,
+        opcode: u8,
+        name: &'static str,
+        params: u8,
+        action: A,
+    ) -> Option<Operation<W>>
+    where
+        A: OpAction<W> + Send + Sync + 'static,
+    {
+        let replaced = self.register(Operation {
+            opcode,
+            name,
+            params,
+            action: |rt, _params| Err(unregistered(rt)),
+        });
+        if self.actions.len() <= opcode as usize {
+            self.actions.resize(opcode as usize + 1, None);
+        }
+        self.actions[opcode as usize] = Some(Arc::new(action));
+        replaced
+    }
+    // remove an operation, so its opcode is an UnknownOpcode error
+    pub fn unregister(&mut self, opcode: u8) -> Option<Operation<W>> {
+        if let Some(action) = self.actions.get_mut(opcode as usize) {
+            *action = None;
+        }
+        self.table.get_mut(opcode as usize)?.take()
+    }
+    pub fn get(&self, opcode: Word) -> Option<Operation<W>> {
+        if (0..100).contains(&opcode) {
+            self.table[opcode as usize]
+        } else {
+            None
         }
-        Operations { ops }
-    }
-    pub fn parse(&self, rt: &Runtime) -> Result<Instruction, String> {
-        // if rt.trace && !rt.trace_state {
-        //     println!("{}   parse instruction", rt.id);
-        // }
-        let mut word = rt.get_word(0)?;
-        let opcode = word % 100;
-        let operation = self
-            .ops
-            .get(&(opcode as Opcode))
-            .ok_or(format!("No such opcode {}", opcode))?;
-        word /= 100;
-
-        let mut params = Vec::new();
-        for i in 0..operation.params {
-            params.push(match word % 10 {
-                0 => Param::Position(rt.get_word(i as Word + 1)?),
-                1 => Param::Immediate(rt.get_word(i as Word + 1)?),
-                2 => Param::Relative(rt.get_word(i as Word + 1)?),
-                _ => return Err("Unknown parameter type".to_string()),
-            });
+    }
+    fn action(&self, opcode: u8) -> Option<SharedAction<W>> {
+        self.actions.get(opcode as usize)?.clone()
+    }
+    pub(crate) fn has_actions(&self) -> bool {
+        self.actions.iter().any(Option::is_some)
+    }
+    pub fn by_name(&self, name: &str) -> Option<Operation<W>> {
+        self.table
+            .iter()
+            .flatten()
+            .find(|op| op.name == name)
+            .copied()
+    }
+
+    pub fn parse(&self, rt: &Runtime<W>) -> Result<Instruction<W>, IntcodeError<W>> {
+        self.decode(rt.pc, |n| rt.get_word(n))
+    }
+
+    // decode the instruction at pc, where fetch(n) gives the word at pc+n
+    pub fn decode<F>(&self, pc: Word, fetch: F) -> Result<Instruction<W>, IntcodeError<W>>
+    where
+        F: Fn(Word) -> Result<W, IntcodeError<W>>,
+    {
+        let raw = fetch(0)?;
+        let low = raw.low_digits(2 + MAX_PARAMS as u32);
+        let opcode = low % 100;
+        let operation = match self.get(opcode) {
+            Some(op) => op,
+            None => {
+                return Err(IntcodeError::UnknownOpcode {
+                    pc,
+                    word: raw,
+                    opcode,
+                })
+            }
+        };
+        let mut word = low / 100;
+        let addr = |val: W| match val.to_word() {
+            Some(addr) => Ok(addr),
+            None => Err(IntcodeError::AddressOutOfRange { pc, value: val }),
+        };
+
+        let mut params = unused_params();
+        for (i, param) in params
+            .iter_mut()
+            .enumerate()
+            .take(operation.params as usize)
+        {
+            let val = fetch(i as Word + 1)?;
+            *param = match word % 10 {
+                0 => Param::Position(addr(val)?),
+                1 => Param::Immediate(val),
+                2 => Param::Relative(addr(val)?),
+                mode => {
+                    return Err(IntcodeError::UnknownParamMode {
+                        pc,
+                        word: raw,
+                        param: i,
+                        mode,
+                    })
+                }
+            };
             word /= 10;
         }
 
@@ -182,74 +338,148 @@ impl Operations {
     }
 }
 
-pub struct Operation {
+impl<W: Int> Default for Operations<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub type Action<W = Word> = fn(&mut Runtime<W>, &[Param<W>]) -> Result<(), IntcodeError<W>>;
+
+pub struct Operation<W = Word> {
     pub opcode: u8,
     pub name: &'static str,
     pub params: u8,
-    pub action: &'static dyn OpAction,
+    pub action: Action<W>,
 }
-impl Operation {
-    fn execute(&self, rt: &mut Runtime, params: &Vec<Param>) -> Result<(), String> {
-        self.action.execute(rt, params)
+impl<W> Operation<W> {
+    fn execute(&self, rt: &mut Runtime<W>, params: &[Param<W>]) -> Result<(), IntcodeError<W>> {
+        (self.action)(rt, params)
     }
 }
-impl PartialEq for Operation {
+// not derived, since that would need W: Copy
+impl<W> Clone for Operation<W> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<W> Copy for Operation<W> {}
+impl<W> PartialEq for Operation<W> {
     fn eq(&self, other: &Self) -> bool {
-        return self.opcode == other.opcode;
+        self.opcode == other.opcode
     }
 }
 
-pub trait OpAction {
-    fn execute(&self, rt: &mut Runtime, params: &Vec<Param>) -> Result<(), String>;
+pub trait OpAction<W = Word> {
+    fn execute(&self, rt: &mut Runtime<W>, params: &[Param<W>]) -> Result<(), IntcodeError<W>>;
+}
+impl<W, F> OpAction<W> for F
+where
+    F: Fn(&mut Runtime<W>, &[Param<W>]) -> Result<(), IntcodeError<W>>,
+{
+    fn execute(&self, rt: &mut Runtime<W>, params: &[Param<W>]) -> Result<(), IntcodeError<W>> {
+        self(rt, params)
+    }
 }
 
-#[derive(PartialEq)]
-pub struct Instruction {
-    pub operation: &'static Operation,
-    pub params: Vec<Param>,
+// A decoded instruction. params is fixed-size so decoding never allocates;
+// only the first operation.params entries are meaningful.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Instruction<W = Word> {
+    pub operation: Operation<W>,
+    params: [Param<W>; MAX_PARAMS],
 }
-impl Instruction {
-    pub fn execute(&self, rt: &mut Runtime) -> Result<(), String> {
-        if rt.trace && !rt.trace_state {
-            println!("{} {:?}", rt.id, self);
+impl<W: Int> Instruction<W> {
+    pub fn new(operation: &Operation<W>, params: &[Param<W>]) -> Self {
+        assert_eq!(operation.params as usize, params.len());
+        let mut p = unused_params();
+        p[..params.len()].clone_from_slice(params);
+        Instruction {
+            operation: *operation,
+            params: p,
         }
-        self.operation.execute(rt, &self.params)
+    }
+    pub fn params(&self) -> &[Param<W>] {
+        &self.params[..self.operation.params as usize]
+    }
+    // number of words the instruction occupies, including the opcode
+    pub fn size(&self) -> usize {
+        self.operation.params as usize + 1
+    }
+    pub fn execute(&self, rt: &mut Runtime<W>) -> Result<(), IntcodeError<W>> {
+        match rt.ops.action(self.operation.opcode) {
+            Some(action) => action.execute(rt, self.params()),
+            None => self.operation.execute(rt, self.params()),
+        }
+    }
+    // only for operations without a registered action, which skips looking
+    // one up
+    pub(crate) fn execute_builtin(&self, rt: &mut Runtime<W>) -> Result<(), IntcodeError<W>> {
+        self.operation.execute(rt, self.params())
     }
 }
-impl std::fmt::Debug for Instruction {
+impl<W: Int> std::fmt::Display for Instruction<W> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{:?}", self.operation.name, self.params)
+        write!(f, "{}", self.operation.name)?;
+        for (i, p) in self.params().iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, p)?;
+        }
+        Ok(())
     }
 }
+impl<W: std::fmt::Debug> std::fmt::Debug for Instruction<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let params = &self.params[..self.operation.params as usize];
+        write!(f, "{}{:?}", self.operation.name, params)
+    }
+}
+
+// filler for the params an operation doesn't take
+fn unused_params<W>() -> [Param<W>; MAX_PARAMS] {
+    std::array::from_fn(|_| Param::Position(0))
+}
 
-#[derive(Debug, PartialEq)]
-pub enum Param {
+// Positions and relative offsets are addresses, so always Words; only
+// immediates have the machine's value type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Param<W = Word> {
     Position(Word),
-    Immediate(Word),
+    Immediate(W),
     Relative(Word),
 }
-impl Param {
-    pub fn resolve(&self, rt: &Runtime) -> Result<Word, String> {
+impl<W: Int> Param<W> {
+    pub fn resolve(&self, rt: &Runtime<W>) -> Result<W, IntcodeError<W>> {
         match self {
             Param::Position(addr) => rt.get(*addr),
-            Param::Immediate(val) => Ok(*val),
-            Param::Relative(val) => rt.get(rt.relative_base + *val),
+            Param::Immediate(val) => Ok(val.clone()),
+            Param::Relative(val) => rt.get(rt.offset(rt.relative_base, *val)?),
         }
     }
-    pub fn position(&self, rt: &Runtime) -> Result<Word, String> {
+    // index is this param's position within the instruction, for error reporting
+    pub fn position(&self, rt: &Runtime<W>, index: usize) -> Result<Word, IntcodeError<W>> {
         match self {
             Param::Position(addr) => Ok(*addr),
-            Param::Relative(addr) => Ok(rt.relative_base + *addr),
-            _ => Err("must be positional/relative parameter".to_string()),
+            Param::Relative(addr) => rt.offset(rt.relative_base, *addr),
+            Param::Immediate(_) => {
+                let word = rt.get_word(0)?;
+                Err(IntcodeError::ImmediateWrite {
+                    pc: rt.pc,
+                    opcode: word.low_digits(2),
+                    word,
+                    param: index,
+                })
+            }
         }
     }
 }
-
-trait ErrWrapExt<T> {
-    fn wrap(self, s: impl std::fmt::Display) -> Result<T, String>;
-}
-impl<T> ErrWrapExt<T> for Result<T, String> {
-    fn wrap(self, s: impl std::fmt::Display) -> Result<T, String> {
-        self.or_else(|e| Err(format!("{}: {}", s, e)))
+impl<W: Int> std::fmt::Display for Param<W> {
+    // #imm, [pos] and rb+n, as the disassembler prints them
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Param::Position(addr) => write!(f, "[{}]", addr),
+            Param::Immediate(val) => write!(f, "#{}", val),
+            Param::Relative(off) if *off < 0 => write!(f, "rb{}", off),
+            Param::Relative(off) => write!(f, "rb+{}", off),
+        }
     }
 }
@@ -0,0 +1,154 @@
+use super::ops::{Instruction, Operation, Operations};
+use super::{disasm, Int, Program, Runtime, Word};
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// how many of the busiest addresses to list in a report
+const TOP_N: usize = 10;
+
+// Execution counts collected while profiling is enabled on a Runtime.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub pc_hits: HashMap<Word, u64>,
+    pub opcode_hits: HashMap<u8, u64>,
+    pub reads: HashMap<Word, u64>,
+    pub writes: HashMap<Word, u64>,
+    // (jump pc, target) for taken jumps to the same or an earlier address
+    pub back_edges: HashMap<(Word, Word), u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HotLoop {
+    pub from: Word,
+    pub to: Word,
+    pub iterations: u64,
+}
+
+impl Profile {
+    pub fn instructions(&self) -> u64 {
+        self.pc_hits.values().sum()
+    }
+
+    // back-edges, busiest first
+    pub fn hot_loops(&self) -> Vec<HotLoop> {
+        let mut loops: Vec<_> = self
+            .back_edges
+            .iter()
+            .map(|(&(from, to), &iterations)| HotLoop {
+                from,
+                to,
+                iterations,
+            })
+            .collect();
+        loops.sort_by_key(|l| (std::cmp::Reverse(l.iterations), l.from));
+        loops
+    }
+
+    // Summary tables followed by the disassembled program with hit counts.
+    // Executed addresses that the linear disassembly doesn't line up with are
+    // listed separately at the end.
+    pub fn report<W: Int>(&self, prog: &Program<W>) -> String {
+        self.report_with(prog, &Operations::new())
+    }
+
+    // like report, naming and disassembling with ops, e.g. a runtime's
+    // operations()
+    pub fn report_with<W: Int>(&self, prog: &Program<W>, ops: &Operations<W>) -> String {
+        let mut out = String::new();
+        writeln!(out, "instructions executed: {}", self.instructions()).unwrap();
+
+        writeln!(out, "\nby opcode:").unwrap();
+        for (opcode, hits) in top(&self.opcode_hits, usize::MAX) {
+            let name = ops.get(opcode as Word).map_or("?", |op| op.name);
+            writeln!(out, "  {:<22} {:>10}", name, hits).unwrap();
+        }
+
+        writeln!(out, "\nhot loops:").unwrap();
+        for l in self.hot_loops().iter().take(TOP_N) {
+            writeln!(out, "  {:04} -> {:04}  {:>10}", l.from, l.to, l.iterations).unwrap();
+        }
+
+        for (title, counts) in &[("reads", &self.reads), ("writes", &self.writes)] {
+            writeln!(out, "\nmost {}:", title).unwrap();
+            for (addr, hits) in top(counts, TOP_N) {
+                writeln!(out, "  [{}] {:>10}", addr, hits).unwrap();
+            }
+        }
+
+        writeln!(out).unwrap();
+        let lines = disasm::disassemble_with(prog, ops);
+        for line in &lines {
+            let hits = match self.pc_hits.get(&line.addr) {
+                Some(n) => n.to_string(),
+                None => String::new(),
+            };
+            writeln!(out, "{:>10}  {:04}: {}", hits, line.addr, line.text()).unwrap();
+        }
+
+        let starts: Vec<Word> = lines.iter().map(|l| l.addr).collect();
+        let mut stray: Vec<_> = self
+            .pc_hits
+            .iter()
+            .filter(|(pc, _)| starts.binary_search(pc).is_err())
+            .collect();
+        if !stray.is_empty() {
+            stray.sort();
+            writeln!(out, "\nalso executed:").unwrap();
+            for (pc, hits) in stray {
+                writeln!(out, "{:>10}  {:04}", hits, pc).unwrap();
+            }
+        }
+        out
+    }
+}
+
+// highest counts first, ties broken by key
+fn top<K: Copy + Ord>(counts: &HashMap<K, u64>, n: usize) -> Vec<(K, u64)> {
+    let mut v: Vec<_> = counts.iter().map(|(k, c)| (*k, *c)).collect();
+    v.sort_by_key(|&(k, c)| (std::cmp::Reverse(c), k));
+    v.truncate(n);
+    v
+}
+
+fn bump<K: std::hash::Hash + Eq>(counts: &mut HashMap<K, u64>, key: K) {
+    *counts.entry(key).or_insert(0) += 1;
+}
+
+impl<W: Int> Runtime<W> {
+    // start (or restart) collecting a profile
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(RefCell::new(Profile::default()));
+    }
+    pub fn disable_profiling(&mut self) -> Option<Profile> {
+        self.profile.take().map(RefCell::into_inner)
+    }
+    pub fn profile(&self) -> Option<Ref<'_, Profile>> {
+        self.profile.as_ref().map(RefCell::borrow)
+    }
+
+    // called after inst has executed; jump is where it jumped to, if anywhere
+    pub(crate) fn profile_instruction(&self, pc: Word, inst: &Instruction<W>, jump: Option<Word>) {
+        if let Some(p) = &self.profile {
+            let mut p = p.borrow_mut();
+            bump(&mut p.pc_hits, pc);
+            bump(&mut p.opcode_hits, inst.operation.opcode);
+            if let Some(to) = jump {
+                let op = inst.operation;
+                if to <= pc && (op == Operation::JIT || op == Operation::JIF) {
+                    bump(&mut p.back_edges, (pc, to));
+                }
+            }
+        }
+    }
+    pub(crate) fn count_read(&self, addr: Word) {
+        if let Some(p) = &self.profile {
+            bump(&mut p.borrow_mut().reads, addr);
+        }
+    }
+    pub(crate) fn count_write(&self, addr: Word) {
+        if let Some(p) = &self.profile {
+            bump(&mut p.borrow_mut().writes, addr);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use super::io::next_char;
+use super::{Int, IntcodeError, Program, Runtime, RuntimeState, Word};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+
+// Play an ASCII program from the terminal. Its output is shown as it
+// arrives, and each line typed is sent once Enter is pressed, so it can be
+// edited first with the terminal's own line editing (backspace, ctrl-U,
+// ctrl-W). Values outside ASCII are shown as numbers on their own line and
+// returned at the end.
+//
+//   let answers = Session::new(&prog).transcript("play.log")?.run()?;
+pub struct Session<W = Word> {
+    rt: Runtime<W>,
+    prompt: String,
+    // a copy of everything shown, typed lines included
+    transcript: Option<Box<dyn Write>>,
+}
+
+impl<W: Int> Session<W> {
+    pub fn new(prog: &Program<W>) -> Self {
+        Session {
+            rt: prog.new_runtime(),
+            prompt: "> ".to_string(),
+            transcript: None,
+        }
+    }
+    pub fn with_prompt(mut self, prompt: &str) -> Self {
+        self.prompt = prompt.to_string();
+        self
+    }
+    // log the session to a file, replacing anything already there
+    pub fn transcript<P: AsRef<Path>>(self, path: P) -> Result<Self, IntcodeError<W>> {
+        let file = File::create(path).map_err(io_err)?;
+        Ok(self.transcript_to(BufWriter::new(file)))
+    }
+    pub fn transcript_to<T: Write + 'static>(mut self, out: T) -> Self {
+        self.transcript = Some(Box::new(out));
+        self
+    }
+    pub fn runtime(&self) -> &Runtime<W> {
+        &self.rt
+    }
+
+    // Play on stdin and stdout until the program halts. Ending input early
+    // (ctrl-D) is InputExhausted.
+    pub fn run(&mut self) -> Result<Vec<W>, IntcodeError<W>> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.run_with(&mut stdin.lock(), &mut stdout.lock())
+    }
+
+    // like run, but typed lines come from input and everything else goes to
+    // screen. Typed lines aren't echoed to screen, the terminal does that.
+    pub fn run_with(
+        &mut self,
+        input: &mut dyn BufRead,
+        screen: &mut dyn Write,
+    ) -> Result<Vec<W>, IntcodeError<W>> {
+        let result = self.play(input, screen);
+        if let Some(t) = &mut self.transcript {
+            t.flush().map_err(io_err)?;
+        }
+        result
+    }
+
+    fn play(
+        &mut self,
+        input: &mut dyn BufRead,
+        screen: &mut dyn Write,
+    ) -> Result<Vec<W>, IntcodeError<W>> {
+        let mut numbers = Vec::new();
+        let mut pending = VecDeque::new();
+        let mut val = None;
+        loop {
+            match self.rt.resume(val.take())? {
+                RuntimeState::Resumable(Some(x)) => match x.to_word() {
+                    Some(c @ 0..=127) => self.show(screen, &(c as u8 as char).to_string())?,
+                    _ => {
+                        self.show(screen, &format!("{}\n", x))?;
+                        numbers.push(x);
+                    }
+                },
+                RuntimeState::Resumable(None) => {
+                    while pending.is_empty() {
+                        let line = self.ask(input, screen)?;
+                        if line.is_ascii() {
+                            pending.extend(line.chars());
+                        } else {
+                            self.show(screen, "only ASCII can be sent\n")?;
+                        }
+                    }
+                    val = Some(next_char(&mut pending)?);
+                }
+                RuntimeState::Complete => return Ok(numbers),
+                RuntimeState::Break(bp) => {
+                    return Err(IntcodeError::UnexpectedBreak { breakpoint: bp })
+                }
+                RuntimeState::OutOfFuel => return Err(self.rt.out_of_fuel()),
+            }
+        }
+    }
+
+    // prompt for a line, newline included
+    fn ask(
+        &mut self,
+        input: &mut dyn BufRead,
+        screen: &mut dyn Write,
+    ) -> Result<String, IntcodeError<W>> {
+        let prompt = self.prompt.clone();
+        self.show(screen, &prompt)?;
+        screen.flush().map_err(io_err)?;
+        let mut line = String::new();
+        if input.read_line(&mut line).map_err(io_err)? == 0 {
+            return Err(IntcodeError::InputExhausted);
+        }
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        if let Some(t) = &mut self.transcript {
+            t.write_all(line.as_bytes()).map_err(io_err)?;
+        }
+        Ok(line)
+    }
+
+    fn show(&mut self, screen: &mut dyn Write, text: &str) -> Result<(), IntcodeError<W>> {
+        screen.write_all(text.as_bytes()).map_err(io_err)?;
+        if let Some(t) = &mut self.transcript {
+            t.write_all(text.as_bytes()).map_err(io_err)?;
+        }
+        Ok(())
+    }
+}
+
+fn io_err<W>(e: io::Error) -> IntcodeError<W> {
+    IntcodeError::Io(e.to_string())
+}
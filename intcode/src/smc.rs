@@ -0,0 +1,94 @@
+use super::{Int, IntcodeError, Runtime, Word};
+use std::collections::HashSet;
+
+// What to do when the program writes over code it has already executed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmcMode {
+    // keep a list, see Runtime::code_writes
+    Report,
+    // fail the writing instruction with IntcodeError::SelfModifyingCode
+    Error,
+}
+
+// pc wrote new over old at addr, which had been executed as part of an
+// instruction (opcode or operand)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CodeWrite<W = Word> {
+    pub pc: Word,
+    pub addr: Word,
+    pub old: W,
+    pub new: W,
+}
+
+#[derive(Clone, Debug)]
+pub struct SmcDetector<W> {
+    mode: SmcMode,
+    // every address that's been part of an executed instruction
+    executed: HashSet<Word>,
+    writes: Vec<CodeWrite<W>>,
+    // the instruction currently executing, or the input instruction still
+    // waiting for its value. None between instructions, and only consulted
+    // while executing, so writes from outside the program (like day2's noun
+    // and verb) aren't counted.
+    writer: Option<Word>,
+}
+
+impl<W: Int> Runtime<W> {
+    // Start tracking executed code. Only instructions executed from here on
+    // count as code.
+    pub fn detect_self_modification(&mut self, mode: SmcMode) {
+        self.smc = Some(SmcDetector {
+            mode,
+            executed: HashSet::new(),
+            writes: Vec::new(),
+            writer: None,
+        });
+    }
+    pub fn stop_detecting_self_modification(&mut self) {
+        self.smc = None;
+    }
+    // writes to executed code seen so far, oldest first
+    pub fn code_writes(&self) -> &[CodeWrite<W>] {
+        match &self.smc {
+            Some(d) => &d.writes,
+            None => &[],
+        }
+    }
+
+    pub(crate) fn smc_begin(&mut self, pc: Word, size: usize) {
+        if let Some(d) = &mut self.smc {
+            // the instruction was fetched, so none of these overflow
+            d.executed.extend((0..size as Word).map(|i| pc + i));
+            d.writer = Some(pc);
+        }
+    }
+    pub(crate) fn smc_end(&mut self) {
+        let waiting = self.read_addr.is_some();
+        if let Some(d) = &mut self.smc {
+            if !waiting {
+                d.writer = None;
+            }
+        }
+    }
+    pub(crate) fn smc_write(&mut self, addr: Word, new: &W) -> Result<(), IntcodeError<W>> {
+        let d = match &mut self.smc {
+            Some(d) => d,
+            None => return Ok(()),
+        };
+        let pc = match d.writer {
+            Some(pc) if self.executing && d.executed.contains(&addr) => pc,
+            _ => return Ok(()),
+        };
+        if d.mode == SmcMode::Error {
+            return Err(IntcodeError::SelfModifyingCode { pc, addr });
+        }
+        let old = self.mem.get(addr as usize);
+        d.writes.push(CodeWrite {
+            pc,
+            addr,
+            old,
+            new: new.clone(),
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use super::mem::Memory;
+use super::ops::Arithmetic;
+use super::{Breakpoint, Int, IntcodeError, Runtime, RuntimeState, Word};
+use std::path::Path;
+use std::str::FromStr;
+
+// A snapshot is plain text, one `key value` per line after a version header:
+//
+//   intcode-snapshot 1
+//   pc 27
+//   rb 1000
+//   state input
+//   read_addr 1000
+//   skip_break 0
+//   executed 1234
+//   mem 109,1,204,-1,...
+//   sparse 1099511627776:5
+//
+// read_addr, watch_hit, arithmetic (checked if missing), fuel, executed,
+// sparse and id are optional. Breakpoints, trace settings and any deadline
+// are not saved; they belong to whoever is driving the machine.
+
+const HEADER: &str = "intcode-snapshot 1";
+
+impl<W: Int> Runtime<W> {
+    pub fn snapshot(&self) -> String {
+        let mut out = vec![HEADER.to_string()];
+        out.push(format!("pc {}", self.pc));
+        out.push(format!("rb {}", self.relative_base));
+        out.push(format!("state {}", format_state(&self.state)));
+        if let Some(addr) = self.read_addr {
+            out.push(format!("read_addr {}", addr));
+        }
+        out.push(format!("skip_break {}", self.skip_break as u8));
+        if let Some(bp) = self.watch_hit.get() {
+            out.push(format!("watch_hit {}", format_breakpoint(bp)));
+        }
+        match self.arithmetic {
+            Arithmetic::Checked => (),
+            Arithmetic::Wrapping => out.push("arithmetic wrapping".to_string()),
+            Arithmetic::Saturating => out.push("arithmetic saturating".to_string()),
+        }
+        if let Some(fuel) = self.fuel() {
+            out.push(format!("fuel {}", fuel));
+        }
+        out.push(format!("executed {}", self.executed));
+        if !self.id.is_empty() {
+            out.push(format!("id {}", escape(&self.id)));
+        }
+        out.push(format!("mem {}", join(self.mem.dense().iter())));
+        let sparse = self.mem.sparse();
+        if !sparse.is_empty() {
+            let pairs = sparse.iter().map(|(a, w)| format!("{}:{}", a, w));
+            out.push(format!("sparse {}", join(pairs)));
+        }
+        out.join("\n") + "\n"
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), IntcodeError<W>> {
+        std::fs::write(path, self.snapshot()).map_err(|e| IntcodeError::Io(e.to_string()))
+    }
+}
+
+impl Runtime {
+    // for other word types, parse the snapshot: `s.parse::<Runtime<i128>>()`
+    pub fn restore(snapshot: &str) -> Result<Runtime, IntcodeError> {
+        snapshot.parse()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Runtime, IntcodeError> {
+        let s = std::fs::read_to_string(path).map_err(|e| IntcodeError::Io(e.to_string()))?;
+        Runtime::restore(&s)
+    }
+}
+
+impl<W: Int> FromStr for Runtime<W> {
+    type Err = IntcodeError<W>;
+
+    fn from_str(snapshot: &str) -> Result<Self, Self::Err> {
+        let mut lines = snapshot.lines().enumerate().map(|(i, l)| (i + 1, l));
+        match lines.next() {
+            Some((_, HEADER)) => (),
+            _ => return Err(err(1, format!("expected '{}'", HEADER))),
+        }
+
+        let mut rt = Runtime::from(Vec::new());
+        let (mut pc, mut rb, mut state, mut mem) = (None, None, None, None);
+        for (n, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let mut kv = line.splitn(2, ' ');
+            let key = kv.next().unwrap_or("");
+            let val = kv.next().unwrap_or("");
+            match key {
+                "pc" => pc = Some(number(n, val)?),
+                "rb" => rb = Some(number(n, val)?),
+                "state" => state = Some(parse_state(n, val)?),
+                "read_addr" => rt.read_addr = Some(number(n, val)?),
+                "skip_break" => rt.skip_break = number::<u8, W>(n, val)? != 0,
+                "watch_hit" => rt.watch_hit.set(Some(parse_breakpoint(n, val)?)),
+                "arithmetic" => {
+                    rt.arithmetic = match val {
+                        "checked" => Arithmetic::Checked,
+                        "wrapping" => Arithmetic::Wrapping,
+                        "saturating" => Arithmetic::Saturating,
+                        _ => return Err(err(n, format!("invalid arithmetic '{}'", val))),
+                    }
+                }
+                "fuel" => rt.set_fuel(Some(number(n, val)?)),
+                "executed" => rt.executed = number(n, val)?,
+                "id" => rt.id = unescape(n, val)?,
+                "mem" => {
+                    let words = split(val)
+                        .map(|w| value(n, w))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    mem = Some(Memory::new(words));
+                }
+                "sparse" => {
+                    let m = mem.as_mut().ok_or_else(|| err(n, "sparse before mem"))?;
+                    for pair in split(val) {
+                        let mut aw = pair.splitn(2, ':');
+                        let addr: Word = number(n, aw.next().unwrap_or(""))?;
+                        let word = value(n, aw.next().unwrap_or(""))?;
+                        if addr < 0 {
+                            return Err(err(n, format!("negative address {}", addr)));
+                        }
+                        m.set(addr as usize, word);
+                    }
+                }
+                _ => return Err(err(n, format!("unknown key '{}'", key))),
+            }
+        }
+
+        let missing = |key| err(0, format!("missing '{}'", key));
+        rt.pc = pc.ok_or_else(|| missing("pc"))?;
+        rt.relative_base = rb.ok_or_else(|| missing("rb"))?;
+        rt.state = state.ok_or_else(|| missing("state"))?;
+        rt.mem = mem.ok_or_else(|| missing("mem"))?;
+        Ok(rt)
+    }
+}
+
+fn format_state<W: Int>(state: &Option<RuntimeState<W>>) -> String {
+    match state {
+        None => "new".to_string(),
+        Some(RuntimeState::Resumable(None)) => "input".to_string(),
+        Some(RuntimeState::Resumable(Some(x))) => format!("output {}", x),
+        Some(RuntimeState::Break(bp)) => format!("break {}", format_breakpoint(*bp)),
+        Some(RuntimeState::Complete) => "complete".to_string(),
+        Some(RuntimeState::OutOfFuel) => "out-of-fuel".to_string(),
+    }
+}
+
+fn parse_state<W: Int>(n: usize, s: &str) -> Result<Option<RuntimeState<W>>, IntcodeError<W>> {
+    let mut parts = s.splitn(2, ' ');
+    let state = match (parts.next().unwrap_or(""), parts.next()) {
+        ("new", None) => None,
+        ("input", None) => Some(RuntimeState::Resumable(None)),
+        ("output", Some(x)) => Some(RuntimeState::Resumable(Some(value(n, x)?))),
+        ("break", Some(bp)) => Some(RuntimeState::Break(parse_breakpoint(n, bp)?)),
+        ("complete", None) => Some(RuntimeState::Complete),
+        ("out-of-fuel", None) => Some(RuntimeState::OutOfFuel),
+        _ => return Err(err(n, format!("invalid state '{}'", s))),
+    };
+    Ok(state)
+}
+
+fn format_breakpoint(bp: Breakpoint) -> String {
+    match bp {
+        Breakpoint::Pc(a) => format!("pc {}", a),
+        Breakpoint::Read(a) => format!("read {}", a),
+        Breakpoint::Write(a) => format!("write {}", a),
+        Breakpoint::Opcode(op) => format!("opcode {}", op),
+        Breakpoint::Step => "step".to_string(),
+    }
+}
+
+fn parse_breakpoint<W>(n: usize, s: &str) -> Result<Breakpoint, IntcodeError<W>> {
+    let mut parts = s.splitn(2, ' ');
+    let bp = match (parts.next().unwrap_or(""), parts.next()) {
+        ("pc", Some(a)) => Breakpoint::Pc(number(n, a)?),
+        ("read", Some(a)) => Breakpoint::Read(number(n, a)?),
+        ("write", Some(a)) => Breakpoint::Write(number(n, a)?),
+        ("opcode", Some(op)) => Breakpoint::Opcode(number(n, op)?),
+        ("step", None) => Breakpoint::Step,
+        _ => return Err(err(n, format!("invalid breakpoint '{}'", s))),
+    };
+    Ok(bp)
+}
+
+fn join<T: ToString>(items: impl Iterator<Item = T>) -> String {
+    items.map(|i| i.to_string()).collect::<Vec<_>>().join(",")
+}
+
+// ids are free text, so backslashes and line breaks are escaped to keep
+// them on one line
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape<W>(n: usize, s: &str) -> Result<String, IntcodeError<W>> {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(c) => return Err(err(n, format!("invalid escape '\\{}'", c))),
+            None => return Err(err(n, "unfinished escape at end of line")),
+        }
+    }
+    Ok(out)
+}
+
+fn split(s: &str) -> impl Iterator<Item = &str> {
+    s.split(',').filter(|w| !w.is_empty())
+}
+
+fn number<T: FromStr, W>(n: usize, s: &str) -> Result<T, IntcodeError<W>> {
+    s.trim()
+        .parse()
+        .map_err(|_| err(n, format!("invalid number '{}'", s)))
+}
+
+// a memory or output value, as opposed to an address or counter
+fn value<W: Int>(n: usize, s: &str) -> Result<W, IntcodeError<W>> {
+    W::parse(s.trim()).map_err(|_| err(n, format!("invalid number '{}'", s)))
+}
+
+fn err<W>(line: usize, message: impl Into<String>) -> IntcodeError<W> {
+    IntcodeError::Snapshot {
+        line,
+        message: message.into(),
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use std::sync::{Arc, Mutex};
 
 #[test]
 fn day2_examples() {
@@ -38,18 +39,18 @@ fn day5_example1() {
 #[test]
 fn day5_example2() {
     let mut rt = Program::parse("1002,4,3,4,33").unwrap().new_runtime();
-    rt.trace = true;
+    rt.set_tracer(trace::TextSink::stdout());
 
     // test that we parse correctly
     let inst = rt.read_instruction().unwrap();
-    let expected = ops::Instruction {
-        operation: &ops::OP_MUL,
-        params: vec![
+    let expected = ops::Instruction::new(
+        &ops::OP_MUL,
+        &[
             ops::Param::Position(4),
             ops::Param::Immediate(3),
             ops::Param::Position(4),
         ],
-    };
+    );
     assert_eq!(expected, inst);
 
     // test that we execute correctly
@@ -63,22 +64,16 @@ fn day9_example0() {
 
     // if the relative base is 2000, then after the instruction 109,19, the relative base would be 2019.
     rt.relative_base = 2000;
-    ops::Instruction {
-        operation: &ops::OP_RBO,
-        params: vec![ops::Param::Immediate(19)],
-    }
-    .execute(&mut rt)
-    .unwrap();
+    ops::Instruction::new(&ops::OP_RBO, &[ops::Param::Immediate(19)])
+        .execute(&mut rt)
+        .unwrap();
     assert_eq!(rt.relative_base, 2019);
 
     // If the next instruction were 204,-34, then the value at address 1985 would be output
     rt.set(1985, 42).unwrap();
-    ops::Instruction {
-        operation: &ops::OP_OUT,
-        params: vec![ops::Param::Relative(-34)],
-    }
-    .execute(&mut rt)
-    .unwrap();
+    ops::Instruction::new(&ops::OP_OUT, &[ops::Param::Relative(-34)])
+        .execute(&mut rt)
+        .unwrap();
     assert_eq!(rt.state, Some(RuntimeState::Resumable(Some(42))));
 }
 
@@ -112,3 +107,1595 @@ fn day9_example3() {
         .unwrap();
     assert_eq!(1125899906842624, out[0]);
 }
+
+#[test]
+fn errors() {
+    use std::error::Error;
+
+    match Program::parse("1,0,x,0,99") {
+        Err(IntcodeError::Parse { index, token, .. }) => {
+            assert_eq!(2, index);
+            assert_eq!("x", token);
+        }
+        r => panic!("expected parse error, got {:?}", r.map(|_| ())),
+    }
+
+    let err = Program::parse("1,0,0,0,42").unwrap().run(0, 0).unwrap_err();
+    assert_eq!(
+        IntcodeError::UnknownOpcode {
+            pc: 4,
+            word: 42,
+            opcode: 42
+        },
+        err
+    );
+
+    let err = Program::parse("301,0,0,0,99")
+        .unwrap()
+        .run(0, 0)
+        .unwrap_err();
+    assert_eq!(
+        IntcodeError::UnknownParamMode {
+            pc: 0,
+            word: 301,
+            param: 0,
+            mode: 3
+        },
+        err
+    );
+
+    // write through an immediate param gets the op name as context
+    let err = Program::parse("11101,1,1,0,99")
+        .unwrap()
+        .run(1, 1)
+        .unwrap_err();
+    assert_eq!(
+        "add: opcode 1 cannot write to immediate param 2 (word 11101) at pc=0",
+        err.to_string()
+    );
+    assert_eq!(
+        &IntcodeError::ImmediateWrite {
+            pc: 0,
+            word: 11101,
+            opcode: 1,
+            param: 2
+        },
+        err.root()
+    );
+    assert_eq!(err.root().to_string(), err.source().unwrap().to_string());
+
+    let err = Program::parse("3,0,3,0,99")
+        .unwrap()
+        .run_collect_output(vec![1])
+        .unwrap_err();
+    assert_eq!(IntcodeError::InputExhausted, err);
+
+    let mut rt = Program::parse("99").unwrap().new_runtime();
+    rt.resume(None).unwrap();
+    assert_eq!(Err(IntcodeError::Halted), rt.resume(None));
+}
+
+#[test]
+fn loading() {
+    let src = "# day 2 example\n\
+               1,9,10,3,\n\
+               2,3,11,0,\n\
+               99,   # halt\n\
+               30 40 50,\n";
+    let prog = Program::from_reader(src.as_bytes()).unwrap();
+    assert_eq!(Ok(3500), prog.run(9, 10));
+    assert_eq!(
+        Program::parse("1,9,10,3,2,3,11,0,99,30,40,50")
+            .unwrap()
+            .operations,
+        prog.operations
+    );
+
+    let err = |src: &str| match Program::parse(src) {
+        Err(IntcodeError::Parse {
+            index,
+            offset,
+            token,
+            ..
+        }) => (index, offset, token),
+        r => panic!("expected parse error, got {:?}", r.map(|_| ())),
+    };
+    assert_eq!((2, 7, "x".to_string()), err("1, 0,\n x, 0, 99"));
+    assert_eq!((1, 2, "".to_string()), err("1,,2"));
+    assert_eq!((0, 0, "".to_string()), err(",1"));
+    assert_eq!((1, 2, "2x".to_string()), err("1 2x#3"));
+    assert_eq!(vec![1, 2], Program::parse("1 2#x").unwrap().operations);
+
+    let path = std::env::temp_dir().join(format!("intcode-load-{}.txt", std::process::id()));
+    std::fs::write(&path, "104,7,\n99\n").unwrap();
+    let prog = Program::from_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(Ok(vec![7]), prog.unwrap().run_collect_output(vec![]));
+    match Program::from_file(&path) {
+        Err(IntcodeError::Io(_)) => (),
+        r => panic!("expected io error, got {:?}", r.map(|_| ())),
+    }
+}
+
+#[test]
+fn arithmetic() {
+    use ops::Arithmetic;
+
+    let run = |op: Word, lhs: Word, rhs: Word, a: Arithmetic| {
+        let mut rt = Runtime::new(vec![op, 5, 6, 0, 99, lhs, rhs]);
+        rt.set_arithmetic(a);
+        rt.resume(None)?;
+        rt.get(0)
+    };
+    let max = Word::MAX;
+    assert_eq!(
+        Err(IntcodeError::Overflow {
+            pc: 0,
+            opcode: 1,
+            lhs: max,
+            rhs: 1
+        }),
+        run(1, max, 1, Arithmetic::Checked)
+    );
+    assert_eq!(Ok(Word::MIN), run(1, max, 1, Arithmetic::Wrapping));
+    assert_eq!(Ok(max), run(1, max, 1, Arithmetic::Saturating));
+    assert_eq!(
+        Err(IntcodeError::Overflow {
+            pc: 0,
+            opcode: 2,
+            lhs: max,
+            rhs: -2
+        }),
+        run(2, max, -2, Arithmetic::Checked)
+    );
+    assert_eq!(Ok(2), run(2, max, -2, Arithmetic::Wrapping));
+    assert_eq!(Ok(Word::MIN), run(2, max, -2, Arithmetic::Saturating));
+    // no difference when nothing overflows
+    assert_eq!(Ok(-6), run(2, 3, -2, Arithmetic::Checked));
+
+    // checked is the default, and the policy survives a snapshot
+    let mut rt = Runtime::new(vec![1, 5, 6, 0, 99, max, 1]);
+    assert_eq!(Arithmetic::Checked, rt.arithmetic());
+    rt.set_arithmetic(Arithmetic::Saturating);
+    let mut rt = Runtime::restore(&rt.snapshot()).unwrap();
+    assert_eq!(Arithmetic::Saturating, rt.arithmetic());
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+}
+
+#[test]
+fn word_types() {
+    let day9 = include_str!("../../day9/src/input.txt");
+    let prog: Program<i128> = day9.parse().unwrap();
+    assert_eq!(Ok(vec![3429606717]), prog.run_collect_output(vec![1]));
+
+    // past what an i64 can hold
+    let big = i128::from(Word::MAX);
+    let mut rt = Runtime::from(vec![2, 5, 6, 0, 99, big, 4]);
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+    assert_eq!(Ok(big * 4), rt.get(0));
+    let restored: Runtime<i128> = rt.snapshot().parse().unwrap();
+    assert_eq!(Ok(big * 4), restored.get(0));
+
+    // but addresses are still Words
+    let mut rt = Runtime::from(vec![1105, 1, big * 4]);
+    assert_eq!(
+        Err(IntcodeError::AddressOutOfRange {
+            pc: 0,
+            value: big * 4
+        }),
+        rt.resume(None)
+    );
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn bigint() {
+    use num_bigint::BigInt;
+
+    // values too big even for an i128 parse, and never overflow
+    let huge = BigInt::from(2).pow(200);
+    let src = format!("2,5,6,0,99,{},{}", huge, huge);
+    let prog: Program<BigInt> = src.parse().unwrap();
+    let mut rt = prog.new_runtime();
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+    assert_eq!(Ok(BigInt::from(2).pow(400)), rt.get(0));
+
+    let restored: Runtime<BigInt> = rt.snapshot().parse().unwrap();
+    assert_eq!(Ok(huge), restored.get(5));
+}
+
+#[test]
+fn disassemble() {
+    let prog = Program::parse("1002,4,3,4,33").unwrap();
+    let lines = prog.disassemble();
+    assert_eq!(2, lines.len());
+    assert_eq!(vec![1002, 4, 3, 4], lines[0].words);
+    assert_eq!(None, lines[1].instruction);
+    assert_eq!(
+        "0000: multiply [4], #3, [4]\n0004: .data 33\n",
+        disasm::render(&lines, false)
+    );
+    assert_eq!(
+        "0000: 1002 4 3 4  multiply [4], #3, [4]\n0004: 33          .data 33\n",
+        disasm::render(&lines, true)
+    );
+
+    // relative params, and a truncated instruction at the end
+    let prog = Program::parse("109,19,204,-34,99,1101,1").unwrap();
+    assert_eq!(
+        "0000: relative-base-offset #19\n\
+         0002: output rb-34\n\
+         0004: halt\n\
+         0005: .data 1101\n\
+         0006: .data 1\n",
+        disasm::render(&prog.disassemble(), false)
+    );
+}
+
+#[test]
+fn assemble() {
+    let a = asm::assemble(
+        "
+        ; double the input until it's over 100
+        start:  in [n]
+        loop:   mul [n], #2, [n]
+                lt #100, [n], rb+0      ; rb is 0, so this is [0]
+                jump-if-false [0], #loop
+                out [n]
+                hlt
+        n:      .data 0
+        ",
+    )
+    .unwrap();
+    assert_eq!(Some(&0), a.symbols.get("start"));
+    assert_eq!(Some(&2), a.symbols.get("loop"));
+    assert_eq!(Some(&16), a.symbols.get("n"));
+    assert_eq!(
+        vec![3, 16, 1002, 16, 2, 16, 20107, 100, 16, 0, 1006, 0, 2, 4, 16, 99, 0],
+        a.program.operations
+    );
+    assert_eq!(vec![112], a.program.run_collect_output(vec![7]).unwrap());
+
+    // disassembled output assembles back to the same program
+    let prog = Program::parse("109,19,204,-34,1002,4,3,4,99,33").unwrap();
+    let text: String = prog.disassemble().iter().map(|l| l.text() + "\n").collect();
+    assert_eq!(
+        prog.operations,
+        Program::assemble(&text).unwrap().operations
+    );
+}
+
+#[test]
+fn assemble_errors() {
+    let err = |src| match asm::assemble(src) {
+        Err(IntcodeError::Assemble { line, column, .. }) => (line, column),
+        _ => panic!("expected an assemble error for {:?}", src),
+    };
+    assert_eq!((1, 1), err("frobnicate #1"));
+    assert_eq!((2, 5), err("halt\nadd 1, #2, [3]"));
+    assert_eq!((1, 14), err("add #1, #2, [nowhere]"));
+    assert_eq!((1, 1), err("add #1, #2"));
+    assert_eq!((2, 1), err("a: halt\na: halt"));
+    assert_eq!((1, 9), err(".data x y"));
+    assert_eq!((1, 1), err(".text 1"));
+    // immediates can't be written to
+    assert_eq!((1, 13), err("add #1, #2, #3"));
+    assert_eq!((1, 4), err("in #5"));
+}
+
+#[test]
+fn memory() {
+    let mut rt = Runtime::new(vec![1, 2, 3]);
+
+    // reads past the end are 0, writes grow memory
+    assert_eq!(Ok(0), rt.get(10));
+    rt.set(10, 7).unwrap();
+    assert_eq!(Ok(7), rt.get(10));
+    assert_eq!(Ok(0), rt.get(9));
+    assert_eq!(11, rt.mem.dense().len());
+
+    // very large addresses don't allocate a huge Vec
+    rt.set(1 << 40, 5).unwrap();
+    assert_eq!(Ok(5), rt.get(1 << 40));
+    assert_eq!(Ok(0), rt.get((1 << 40) + 1));
+    assert_eq!(11, rt.mem.dense().len());
+
+    rt.relative_base = -10;
+    assert_eq!(
+        Err(IntcodeError::NegativeAddress {
+            pc: 0,
+            addr: -1,
+            relative_base: -10
+        }),
+        rt.get(-1)
+    );
+
+    // relative writes below zero are an error rather than a silent HashMap key
+    let err = Program::parse("109,-5,203,0,99")
+        .unwrap()
+        .run_collect_output(vec![1])
+        .unwrap_err();
+    assert_eq!(
+        IntcodeError::NegativeAddress {
+            pc: 2,
+            addr: -5,
+            relative_base: -5
+        },
+        err
+    );
+
+    // and so are addresses past the largest Word, rather than overflowing
+    let max = Word::MAX;
+    let oor = |pc, value| IntcodeError::AddressOutOfRange { pc, value };
+    let err = |src: String| {
+        Program::parse(&src)
+            .unwrap()
+            .run_collect_output(vec![])
+            .unwrap_err()
+    };
+    // moving the relative base, reading and writing relative to it
+    assert_eq!(&oor(2, 1), err(format!("109,{},109,1,99", max)).root());
+    assert_eq!(&oor(2, max), err(format!("109,10,204,{},99", max)).root());
+    assert_eq!(
+        &oor(2, max),
+        err(format!("109,10,21101,1,1,{},99", max)).root()
+    );
+    // fetching an instruction's params from the top of memory
+    let mut rt = Runtime::new(vec![1106, 0, max]);
+    rt.set(max, 1).unwrap();
+    assert_eq!(Err(oor(max, 1)), rt.resume(None));
+    // moving on from one, also with smc detection marking it as code
+    let mut rt = Runtime::new(vec![1106, 0, max - 1]);
+    rt.set(max - 1, 104).unwrap();
+    rt.set(max, 5).unwrap();
+    rt.detect_self_modification(SmcMode::Report);
+    assert_eq!(Err(oor(max - 1, 2)), rt.resume(None));
+    // but halting there is fine
+    let mut rt = Runtime::new(vec![1106, 0, max]);
+    rt.set(max, 99).unwrap();
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+}
+
+const COUNTDOWN: &str = "
+        in [n]
+loop:   add [n], #-1, [n]
+        jnz [n], #loop
+        out [n]
+        hlt
+n:      .data 0
+";
+
+#[test]
+fn breakpoints() {
+    let prog = Program::assemble(COUNTDOWN).unwrap();
+
+    // pc breakpoint stops before the instruction, every time it's reached
+    let mut rt = prog.new_runtime();
+    rt.add_breakpoint(Breakpoint::Pc(2));
+    assert_eq!(Ok(RuntimeState::Resumable(None)), rt.resume(None));
+    for n in (1..=3).rev() {
+        assert_eq!(
+            Ok(RuntimeState::Break(Breakpoint::Pc(2))),
+            rt.resume(Some(3))
+        );
+        assert_eq!(2, rt.pc);
+        assert_eq!(Ok(n), rt.get(12));
+    }
+    assert_eq!(Ok(RuntimeState::Resumable(Some(0))), rt.resume(None));
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+
+    // opcode breakpoint
+    let mut rt = prog.new_runtime();
+    rt.add_breakpoint(Breakpoint::Opcode(4));
+    rt.resume(None).unwrap();
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Opcode(4))),
+        rt.resume(Some(1))
+    );
+    assert_eq!(9, rt.pc);
+    assert!(rt.remove_breakpoint(Breakpoint::Opcode(4)));
+    assert_eq!(Ok(RuntimeState::Resumable(Some(0))), rt.resume(None));
+
+    // write watchpoints fire after the write, including the one made by input
+    let mut rt = prog.new_runtime();
+    rt.add_breakpoint(Breakpoint::Write(12));
+    rt.resume(None).unwrap();
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Write(12))),
+        rt.resume(Some(2))
+    );
+    assert_eq!((2, Ok(2)), (rt.pc, rt.get(12)));
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Write(12))),
+        rt.resume(None)
+    );
+    assert_eq!((6, Ok(1)), (rt.pc, rt.get(12)));
+
+    // an output wins over a read watchpoint on the same instruction, which
+    // then fires on the next resume
+    let mut rt = prog.new_runtime();
+    rt.resume(None).unwrap();
+    rt.add_breakpoint(Breakpoint::Read(12));
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Read(12))),
+        rt.resume(Some(1))
+    );
+    assert_eq!(6, rt.pc);
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Read(12))),
+        rt.resume(None)
+    );
+    assert_eq!(9, rt.pc);
+    assert_eq!(Ok(RuntimeState::Resumable(Some(0))), rt.resume(None));
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Read(12))),
+        rt.resume(None)
+    );
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+
+    // helpers that don't expect breaks report them
+    let mut rt = prog.new_runtime();
+    rt.add_breakpoint(Breakpoint::Pc(9));
+    let mut out = Vec::new();
+    assert_eq!(
+        Err(IntcodeError::UnexpectedBreak {
+            breakpoint: Breakpoint::Pc(9)
+        }),
+        rt.run_io(
+            &mut io::IteratorInput::new(vec![1]),
+            &mut io::VecOutput::new(&mut out)
+        )
+    );
+
+    // the caller's own get and set aren't the program's, so don't hit
+    // watchpoints
+    let mut rt = prog.new_runtime();
+    rt.add_breakpoint(Breakpoint::Read(5));
+    rt.add_breakpoint(Breakpoint::Write(1));
+    rt.set(1, 12).unwrap();
+    assert_eq!(Ok(RuntimeState::Resumable(None)), rt.resume(None));
+    assert_eq!(Ok(12), rt.get(5));
+    assert_eq!(Ok(RuntimeState::Resumable(Some(0))), rt.resume(Some(1)));
+}
+
+#[test]
+fn step_instruction() {
+    let mut rt = Program::assemble(COUNTDOWN).unwrap().new_runtime();
+    rt.add_breakpoint(Breakpoint::Pc(2));
+
+    assert_eq!(Ok(RuntimeState::Resumable(None)), rt.step_instruction(None));
+    assert_eq!(2, rt.pc());
+    // stepping onto a breakpoint doesn't stop there twice
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Step)),
+        rt.step_instruction(Some(2))
+    );
+    assert_eq!(6, rt.pc());
+    assert_eq!(Ok(RuntimeState::Break(Breakpoint::Pc(2))), rt.resume(None));
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Step)),
+        rt.step_instruction(None)
+    );
+    assert_eq!(6, rt.pc());
+    assert_eq!(Ok(0), rt.peek(12));
+    assert_eq!(Ok(RuntimeState::Resumable(Some(0))), rt.resume(None));
+}
+
+#[test]
+fn fuel() {
+    let prog = Program::assemble(COUNTDOWN).unwrap();
+    let mut rt = prog.new_runtime();
+    rt.set_fuel(Some(4));
+    assert_eq!(Ok(RuntimeState::Resumable(None)), rt.resume(None));
+    assert_eq!(Ok(RuntimeState::OutOfFuel), rt.resume(Some(3)));
+    assert_eq!((4, 6), (rt.instructions_executed(), rt.pc()));
+    // stays stopped until topped up
+    assert_eq!(Ok(RuntimeState::OutOfFuel), rt.resume(None));
+    rt.add_fuel(3);
+    assert_eq!(Ok(RuntimeState::OutOfFuel), rt.resume(None));
+    assert_eq!(
+        (7, 9, Some(0)),
+        (rt.instructions_executed(), rt.pc(), rt.fuel())
+    );
+
+    // survives a snapshot
+    let mut rt = Runtime::restore(&rt.snapshot()).unwrap();
+    assert_eq!(RuntimeState::OutOfFuel, rt.state());
+    assert_eq!(
+        Err(IntcodeError::OutOfFuel { executed: 7 }),
+        rt.step_read(1)
+    );
+    rt.set_fuel(None);
+    assert_eq!(Ok(Some(vec![0])), rt.step_read(1));
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+    assert_eq!(9, rt.instructions_executed());
+
+    // running out at a breakpoint doesn't stop there again
+    let mut rt = prog.new_runtime();
+    rt.add_breakpoint(Breakpoint::Pc(6));
+    rt.resume(None).unwrap();
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Pc(6))),
+        rt.resume(Some(1))
+    );
+    rt.set_fuel(Some(0));
+    assert_eq!(Ok(RuntimeState::OutOfFuel), rt.resume(None));
+    rt.add_fuel(1);
+    assert_eq!(Ok(RuntimeState::OutOfFuel), rt.resume(None));
+    assert_eq!(9, rt.pc());
+
+    // deadlines
+    let mut rt = prog.new_runtime();
+    rt.set_deadline(Some(std::time::Instant::now()));
+    assert_eq!(Ok(RuntimeState::OutOfFuel), rt.resume(None));
+    assert_eq!(0, rt.instructions_executed());
+    rt.set_deadline(None);
+    assert_eq!(Ok(RuntimeState::Resumable(None)), rt.resume(None));
+
+    // an endless loop
+    let prog = Program::parse("1105,1,0").unwrap();
+    assert_eq!(
+        Err(IntcodeError::OutOfFuel { executed: 100 }),
+        prog.run_limited(1, 0, 100)
+    );
+    let prog = Program::parse("1,9,10,3,2,3,11,0,99,30,40,50").unwrap();
+    assert_eq!(Ok(3500), prog.run_limited(9, 10, 100));
+}
+
+#[test]
+fn snapshots() {
+    let prog = Program::assemble(COUNTDOWN).unwrap();
+
+    // fork at the input prompt
+    let mut rt = Program::parse("3,0,4,0,99").unwrap().new_runtime();
+    assert_eq!(Ok(RuntimeState::Resumable(None)), rt.resume(None));
+    let mut fork = rt.clone();
+    assert_eq!(Ok(RuntimeState::Resumable(Some(1))), rt.resume(Some(1)));
+    assert_eq!(Ok(RuntimeState::Resumable(Some(2))), fork.resume(Some(2)));
+
+    // save while blocked on input, restore and finish
+    let mut rt = prog.new_runtime();
+    rt.resume(None).unwrap();
+    rt.set(1 << 40, 9).unwrap();
+    let snap = rt.snapshot();
+    let mut restored = Runtime::restore(&snap).unwrap();
+    assert_eq!(snap, restored.snapshot());
+    assert_eq!(Ok(9), restored.get(1 << 40));
+    assert_eq!(
+        Ok(RuntimeState::Resumable(Some(0))),
+        restored.resume(Some(4))
+    );
+
+    // stopped at a breakpoint: resumes from exactly that point
+    let mut rt = prog.new_runtime();
+    rt.add_breakpoint(Breakpoint::Pc(2));
+    rt.resume(None).unwrap();
+    rt.resume(Some(2)).unwrap();
+    let path = std::env::temp_dir().join(format!("intcode-snapshot-{}.txt", std::process::id()));
+    rt.save(&path).unwrap();
+    let mut restored = Runtime::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(RuntimeState::Break(Breakpoint::Pc(2)), restored.state());
+    assert_eq!((2, 0), (restored.pc(), restored.relative_base()));
+    restored.add_breakpoint(Breakpoint::Pc(2));
+    assert_eq!(
+        Ok(RuntimeState::Break(Breakpoint::Pc(2))),
+        restored.resume(None)
+    );
+    assert_eq!(Ok(1), restored.get(12));
+    assert_eq!(Ok(RuntimeState::Resumable(Some(0))), restored.resume(None));
+
+    // ids are escaped to stay on their line
+    let mut rt = Program::parse("99").unwrap().new_runtime();
+    rt.id = "amp\\1\nline 2\r".to_string();
+    assert!(rt.snapshot().contains("id amp\\\\1\\nline 2\\r\n"));
+    assert_eq!(rt.id, Runtime::restore(&rt.snapshot()).unwrap().id);
+
+    // a finished program stays finished
+    let mut rt = Program::parse("99").unwrap().new_runtime();
+    rt.resume(None).unwrap();
+    let mut restored = Runtime::restore(&rt.snapshot()).unwrap();
+    assert_eq!(Err(IntcodeError::Halted), restored.resume(None));
+
+    assert_eq!(
+        Err(IntcodeError::Snapshot {
+            line: 3,
+            message: "invalid number 'x'".to_string()
+        }),
+        Runtime::restore("intcode-snapshot 1\npc 0\nrb x\n").map(|_| ())
+    );
+}
+
+#[test]
+fn binary() {
+    let inputs = [
+        include_str!("../../day2/src/input.txt"),
+        include_str!("../../day5/src/input.txt"),
+        include_str!("../../day7/src/input.txt"),
+        include_str!("../../day9/src/input.txt"),
+        include_str!("../../day11/src/input.txt"),
+        include_str!("../../day13/src/input.txt"),
+    ];
+    for src in inputs.iter() {
+        let prog = Program::parse(src).unwrap();
+        let bytes = prog.to_bytes();
+        assert!(bytes.len() < src.len() / 2, "{} bytes", bytes.len());
+        assert_eq!(
+            prog.operations,
+            Program::from_bytes(&bytes).unwrap().operations
+        );
+    }
+    let extremes = vec![0, -1, 1, 63, -64, 64, Word::MAX, Word::MIN];
+    let prog = Program::new(extremes.clone());
+    assert_eq!(
+        extremes,
+        Program::from_bytes(&prog.to_bytes()).unwrap().operations
+    );
+
+    // memory images keep high addresses as segments
+    let mut rt = Program::parse("109,19,204,-34,99").unwrap().new_runtime();
+    for addr in [1 << 40, (1 << 40) + 1, 1 << 50].iter() {
+        rt.set(*addr, -addr).unwrap();
+    }
+    let image = rt.memory_image();
+    assert!(image.len() < 64, "{} bytes", image.len());
+    let restored = Runtime::from_memory_image(&image).unwrap();
+    assert_eq!(rt.snapshot(), restored.snapshot());
+    assert_eq!(Ok(-(1 << 50)), restored.get(1 << 50));
+
+    let err = |bytes: &[u8]| match Program::from_bytes(bytes) {
+        Err(IntcodeError::Binary { offset, .. }) => offset,
+        r => panic!("expected binary error, got {:?}", r.map(|_| ())),
+    };
+    assert_eq!(0, err(b"1,2,3"));
+    assert_eq!(3, err(b"ICB\x02"));
+    // three words promised, two given
+    assert_eq!(4, err(b"ICB\x01\x03\x02\x04"));
+    // trailing garbage
+    assert_eq!(7, err(b"ICB\x01\x01\x02\x00\x00"));
+    // programs can't have segments, even though memory images can
+    assert_eq!(6, err(b"ICB\x01\x01\x02\x01\x80\x01\x01\x02"));
+}
+
+#[test]
+fn custom_operations() {
+    // square [11] into [12], triple it and output it
+    let prog = Program::parse("10,11,12, 1002,12,3,12, 4,12, 99, 0, 7,0").unwrap();
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode {
+            pc: 0,
+            word: 10,
+            opcode: 10
+        }),
+        prog.run_collect_output(vec![])
+    );
+
+    let mut ops = ops::Operations::new();
+    let square = ops::Operation {
+        opcode: 10,
+        name: "square",
+        params: 2,
+        action: |rt, params| {
+            let val = params[0].resolve(rt)?;
+            rt.set(params[1].position(rt, 1)?, val * val)
+        },
+    };
+    assert!(ops.register(square).is_none());
+    // an instrumented multiply
+    let multiplies = Arc::new(Mutex::new(0));
+    let count = multiplies.clone();
+    let replaced = ops.register_action(
+        2,
+        "multiply",
+        3,
+        move |rt: &mut Runtime, params: &[ops::Param]| {
+            *count.lock().unwrap() += 1;
+            (ops::OP_MUL.action)(rt, params)
+        },
+    );
+    assert_eq!(Some("multiply"), replaced.map(|op| op.name));
+    assert_eq!(Some(10), ops.by_name("square").map(|op| op.opcode));
+
+    // the tools know it too, given the table
+    let text = |lines: Vec<disasm::Line>| lines.iter().map(|l| l.text()).collect::<Vec<_>>();
+    assert_eq!(".data 10", text(disasm::disassemble(&prog))[0]);
+    assert_eq!(
+        vec![
+            "square [11], [12]",
+            "multiply [12], #3, [12]",
+            "output [12]"
+        ],
+        text(disasm::disassemble_with(&prog, &ops))[..3].to_vec()
+    );
+    assert_eq!(
+        vec![10, 11, 12],
+        asm::assemble_with("square [11], [12]", &ops)
+            .unwrap()
+            .program
+            .operations
+    );
+    assert!(asm::assemble("square [11], [12]").is_err());
+
+    let mut rt = prog.new_runtime();
+    rt.set_operations(ops);
+    let mut fork = rt.clone();
+    assert_eq!(Ok(RuntimeState::Resumable(Some(147))), rt.resume(None));
+    assert_eq!(Ok(RuntimeState::Resumable(Some(147))), fork.resume(None));
+    assert_eq!(2, *multiplies.lock().unwrap());
+    assert_eq!(
+        "square [11], [12]",
+        rt.operations()
+            .parse(&prog.new_runtime())
+            .unwrap()
+            .to_string()
+    );
+
+    // replacing it again drops the closure
+    let mut ops = rt.operations().clone();
+    ops.register(ops::OP_MUL);
+    ops.unregister(4);
+    let mut rt = prog.new_runtime();
+    rt.set_operations(ops);
+    assert_eq!(
+        Err(IntcodeError::UnknownOpcode {
+            pc: 7,
+            word: 4,
+            opcode: 4
+        }),
+        rt.resume(None)
+    );
+    assert_eq!(2, *multiplies.lock().unwrap());
+}
+
+#[test]
+fn journal() {
+    let prog = Program::assemble(COUNTDOWN).unwrap();
+    let mut rt = prog.new_runtime();
+    rt.enable_journal(None);
+    assert_eq!(Ok(RuntimeState::Resumable(None)), rt.resume(None));
+    assert_eq!(Ok(RuntimeState::Resumable(Some(0))), rt.resume(Some(3)));
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+    // in, 3x (add, jnz), out, hlt
+    assert_eq!(9, rt.journal().count());
+    assert_eq!(Some(2), rt.last_writer(12).map(|e| e.pc));
+
+    // back to just before the output, then a couple more instructions
+    assert_eq!(Some(IoEvent::Output(0)), rt.rewind_to_io());
+    assert_eq!(9, rt.pc);
+    assert_eq!(RuntimeState::Break(Breakpoint::Step), rt.state());
+    assert_eq!(Some(6), rt.step_back().map(|e| e.pc));
+    assert_eq!(Some(2), rt.step_back().map(|e| e.pc));
+    assert_eq!(Ok(1), rt.get(12));
+
+    // all the way back to the input, and run again with a different value
+    assert_eq!(Some(IoEvent::Input(3)), rt.rewind_to_io());
+    assert_eq!((0, Ok(0)), (rt.pc, rt.get(12)));
+    assert_eq!(None, rt.step_back());
+    assert_eq!(Ok(RuntimeState::Resumable(None)), rt.resume(None));
+    assert_eq!(Ok(RuntimeState::Resumable(Some(0))), rt.resume(Some(2)));
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+    assert_eq!(7, rt.journal().count());
+
+    // bounded journal keeps only the most recent entries
+    let mut rt = prog.new_runtime();
+    rt.enable_journal(Some(2));
+    rt.resume(None).unwrap();
+    rt.resume(Some(3)).unwrap();
+    assert_eq!(vec![6, 9], rt.journal().map(|e| e.pc).collect::<Vec<_>>());
+
+    // enabled while waiting for input, the input is still journaled
+    let mut rt = prog.new_runtime();
+    rt.resume(None).unwrap();
+    rt.enable_journal(None);
+    rt.resume(Some(1)).unwrap();
+    let first = rt.journal().next().unwrap();
+    assert_eq!((0, &vec![(12, 0)]), (first.pc, &first.writes));
+    assert_eq!(Some(IoEvent::Output(0)), rt.rewind_to_io());
+    assert_eq!(Some(IoEvent::Input(1)), rt.rewind_to_io());
+    assert_eq!((0, Ok(0)), (rt.pc, rt.get(12)));
+
+    // a failed instruction has nothing to undo, but an input whose write
+    // failed is still waiting and can be sent again
+    let mut rt = Program::parse("1002,5,2,5,99,0").unwrap().new_runtime();
+    rt.set(5, Word::MAX).unwrap();
+    rt.enable_journal(None);
+    assert!(rt.resume(None).is_err());
+    assert_eq!(0, rt.journal().count());
+    let mut rt = Program::parse("3,0,99").unwrap().new_runtime();
+    rt.detect_self_modification(SmcMode::Error);
+    rt.enable_journal(None);
+    rt.resume(None).unwrap();
+    assert!(rt.resume(Some(7)).is_err());
+    assert!(rt.resume(Some(7)).is_err());
+    assert_eq!(vec![0], rt.journal().map(|e| e.pc).collect::<Vec<_>>());
+
+    // relative base is restored too
+    let mut rt = Program::parse("109,5,109,-2,99").unwrap().new_runtime();
+    rt.enable_journal(None);
+    rt.resume(None).unwrap();
+    rt.step_back();
+    rt.step_back();
+    assert_eq!((2, 5), (rt.pc, rt.relative_base()));
+}
+
+#[test]
+fn tracing() {
+    use trace::{JsonLinesSink, RingBuffer, TextSink, TraceEvent};
+    let prog = Program::parse("109,3,3,0,1002,0,2,0,4,0,99").unwrap();
+
+    let ring = Arc::new(Mutex::new(RingBuffer::new(100)));
+    let mut rt = prog.new_runtime();
+    rt.id = "a".to_string();
+    rt.set_shared_tracer(ring.clone());
+    rt.resume(None).unwrap();
+    rt.resume(Some(21)).unwrap();
+    rt.resume(None).unwrap();
+    let events: Vec<_> = ring
+        .lock()
+        .unwrap()
+        .events()
+        .map(|(_, e)| e.clone())
+        .collect();
+    let ops = ops::Operations::new();
+    let inst = |pc| ops.decode(pc, |n| prog.new_runtime().peek(pc + n)).unwrap();
+    assert_eq!(
+        vec![
+            TraceEvent::Instruction {
+                pc: 0,
+                instruction: inst(0)
+            },
+            TraceEvent::RelativeBase { delta: 3, rb: 3 },
+            TraceEvent::Instruction {
+                pc: 2,
+                instruction: inst(2)
+            },
+            TraceEvent::Input { addr: 0, val: 21 },
+            TraceEvent::Write { addr: 0, val: 21 },
+            TraceEvent::Instruction {
+                pc: 4,
+                instruction: inst(4)
+            },
+            TraceEvent::Read { addr: 0, val: 21 },
+            TraceEvent::Write { addr: 0, val: 42 },
+            TraceEvent::Instruction {
+                pc: 8,
+                instruction: inst(8)
+            },
+            TraceEvent::Read { addr: 0, val: 42 },
+            TraceEvent::Output { val: 42 },
+            TraceEvent::Instruction {
+                pc: 10,
+                instruction: inst(10)
+            },
+            TraceEvent::Halt,
+        ],
+        events
+    );
+    assert!(ring.lock().unwrap().events().all(|(id, _)| id == "a"));
+
+    // ring buffer only keeps the most recent events
+    let ring = Arc::new(Mutex::new(RingBuffer::new(2)));
+    let mut rt = prog.new_runtime();
+    rt.set_shared_tracer(ring.clone());
+    rt.run_io(
+        &mut io::IteratorInput::new(vec![21]),
+        &mut io::VecOutput::new(&mut Vec::new()),
+    )
+    .unwrap();
+    assert_eq!(2, ring.lock().unwrap().events().count());
+
+    // text and json lines
+    let text = Arc::new(Mutex::new(TextSink::new(Vec::new())));
+    let json = Arc::new(Mutex::new(JsonLinesSink::new(Vec::new())));
+    for (sink, id) in [
+        (text.clone() as Arc<Mutex<dyn trace::TraceSink + Send>>, ""),
+        (json.clone(), "b\"1"),
+    ]
+    .iter()
+    {
+        let mut rt = Program::parse("1002,4,3,4,33").unwrap().new_runtime();
+        rt.id = id.to_string();
+        rt.set_shared_tracer(sink.clone());
+        rt.resume(None).unwrap();
+    }
+    let text = String::from_utf8(text.lock().unwrap().get_ref().clone()).unwrap();
+    assert_eq!(
+        "0000: multiply [4], #3, [4]\n      read [4] -> 33\n      write [4] <- 99\n0004: halt\n      halt\n",
+        text
+    );
+    let json = String::from_utf8(json.lock().unwrap().get_ref().clone()).unwrap();
+    assert_eq!(
+        Some(
+            r#"{"id":"b\"1","event":"instruction","pc":0,"op":"multiply","text":"multiply [4], #3, [4]"}"#
+        ),
+        json.lines().next()
+    );
+    assert_eq!(
+        Some(r#"{"id":"b\"1","event":"write","addr":4,"val":99}"#),
+        json.lines().nth(2)
+    );
+}
+
+#[test]
+fn profiling() {
+    let prog = Program::assemble(COUNTDOWN).unwrap();
+    let mut rt = prog.new_runtime();
+    rt.enable_profiling();
+    rt.run_io(
+        &mut io::IteratorInput::new(vec![3]),
+        &mut io::VecOutput::new(&mut Vec::new()),
+    )
+    .unwrap();
+    // the caller's own reads and writes aren't counted
+    rt.get(12).unwrap();
+    rt.set(13, 1).unwrap();
+
+    let p = rt.disable_profiling().unwrap();
+    assert_eq!(9, p.instructions());
+    assert_eq!(Some(&3), p.pc_hits.get(&2));
+    assert_eq!(Some(&3), p.opcode_hits.get(&1));
+    assert_eq!(Some(&7), p.reads.get(&12));
+    assert_eq!(Some(&4), p.writes.get(&12));
+    assert_eq!(None, p.writes.get(&13));
+    // the last jnz falls through, so only two trips round the loop
+    assert_eq!(
+        vec![HotLoop {
+            from: 6,
+            to: 2,
+            iterations: 2
+        }],
+        p.hot_loops()
+    );
+
+    let report = p.report(&prog);
+    assert!(report.contains("\n         3  0002: add [12], #-1, [12]\n"));
+    assert!(report.contains("\n            0012: .data 0\n"));
+    assert!(report.contains("\n  0006 -> 0002           2\n"));
+    assert!(rt.profile().is_none());
+}
+
+#[test]
+fn coverage() {
+    let prog = Program::assemble(COUNTDOWN).unwrap();
+    let mut rt = prog.new_runtime();
+    rt.enable_coverage();
+    rt.resume(None).unwrap();
+    rt.resume(Some(1)).unwrap();
+    let cov = rt.disable_coverage().unwrap();
+    assert_eq!(vec![0, 2, 6, 9], cov.executed().collect::<Vec<_>>());
+    let summary = cov.summary(&prog);
+    assert_eq!((5, vec![11]), (summary.total, summary.uncovered.clone()));
+    assert_eq!(
+        r#"{"total":5,"covered":[0,2,6,9],"uncovered":[11],"percent":80.0}"#,
+        summary.to_json()
+    );
+    assert_eq!(
+        [
+            "+ 0000: input [12]",
+            "+ 0002: add [12], #-1, [12]",
+            "+ 0006: jump-if-true [12], #2",
+            "+ 0009: output [12]",
+            "- 0011: halt",
+            "  0012: .data 0",
+            "",
+        ]
+        .join("\n"),
+        cov.listing(&prog)
+    );
+
+    // day5 diagnostics: the air conditioner (1) and thermal radiator (5)
+    // tests exercise different parts of the program
+    let prog = Program::parse(include_str!("../../day5/src/input.txt")).unwrap();
+    let run = |input| {
+        let mut rt = prog.new_runtime();
+        rt.enable_coverage();
+        rt.run_io(
+            &mut io::IteratorInput::new(vec![input]),
+            &mut io::VecOutput::new(&mut Vec::new()),
+        )
+        .unwrap();
+        rt.disable_coverage().unwrap()
+    };
+    let (one, five) = (run(1), run(5));
+    let mut both = one.clone();
+    both.merge(&five);
+    let pct = |c: &Coverage| c.summary(&prog).percent();
+    assert!(pct(&both) > pct(&one) && pct(&both) > pct(&five));
+    assert!(one
+        .executed()
+        .chain(five.executed())
+        .all(|a| both.is_covered(a)));
+}
+
+#[test]
+fn control_flow_graph() {
+    use cfg::Exit;
+    let prog = Program::assemble(COUNTDOWN).unwrap();
+    let cfg = prog.cfg();
+    let summary: Vec<_> = cfg
+        .blocks
+        .values()
+        .map(|b| (b.start, b.instructions.len(), b.exit))
+        .collect();
+    assert_eq!(
+        vec![
+            (0, 1, Exit::Fallthrough(2)),
+            (
+                2,
+                2,
+                Exit::Branch {
+                    taken: 2,
+                    fallthrough: 9
+                }
+            ),
+            (9, 2, Exit::Halt),
+        ],
+        summary
+    );
+    assert_eq!(
+        "digraph cfg {\n\
+         \x20 node [shape=box, fontname=\"monospace\"];\n\
+         \x20 b0 [label=\"0000: input [12]\\l\"];\n\
+         \x20 b2 [label=\"0002: add [12], #-1, [12]\\l0006: jump-if-true [12], #2\\l\"];\n\
+         \x20 b9 [label=\"0009: output [12]\\l0011: halt\\l\"];\n\
+         \x20 b0 -> b2;\n\
+         \x20 b2 -> b2 [label=\"T\"];\n\
+         \x20 b2 -> b9 [label=\"F\"];\n\
+         }\n",
+        cfg.to_dot()
+    );
+
+    // unconditional and indirect jumps, and a jump into garbage
+    let prog = Program::assemble(
+        "
+        jt #1, #skip
+        hlt
+skip:   jz [0], [0]
+        jt #1, #bad
+bad:    .data 42
+        ",
+    )
+    .unwrap();
+    let cfg = prog.cfg();
+    let exits: Vec<_> = cfg.blocks.values().map(|b| (b.start, b.exit)).collect();
+    assert_eq!(
+        vec![
+            (0, Exit::Jump(4)),
+            (
+                4,
+                Exit::Indirect {
+                    fallthrough: Some(7)
+                }
+            ),
+            (7, Exit::Jump(10)),
+            (10, Exit::Invalid(10)),
+        ],
+        exits
+    );
+    assert_eq!(vec![4], cfg.indirect().map(|b| b.start).collect::<Vec<_>>());
+
+    // a call: return address stored at rb+0, then an unconditional jump
+    let prog = Program::assemble(
+        "
+        add #back, #0, rb
+        jt #1, #func
+back:   hlt
+func:   jz #0, rb
+        ",
+    )
+    .unwrap();
+    let exits: Vec<_> = prog
+        .cfg()
+        .blocks
+        .values()
+        .map(|b| (b.start, b.exit))
+        .collect();
+    assert_eq!(
+        vec![
+            (0, Exit::Call { target: 8, ret: 7 }),
+            (7, Exit::Halt),
+            (8, Exit::Indirect { fallthrough: None }),
+        ],
+        exits
+    );
+
+    // a return address is forgotten when rb moves, or at a block start
+    for (src, exits) in &[
+        (
+            "
+        add #back, #0, rb
+        arb #1
+        jt #1, #func
+back:   hlt
+func:   hlt
+            ",
+            vec![(0, Exit::Jump(10)), (10, Exit::Halt)],
+        ),
+        (
+            "
+        jz [100], #mid
+        add #back, #0, rb
+mid:    jt #1, #func
+back:   hlt
+func:   hlt
+            ",
+            vec![
+                (
+                    0,
+                    Exit::Branch {
+                        taken: 7,
+                        fallthrough: 3,
+                    },
+                ),
+                (3, Exit::Fallthrough(7)),
+                (7, Exit::Jump(11)),
+                (11, Exit::Halt),
+            ],
+        ),
+    ] {
+        let cfg = Program::assemble(src).unwrap().cfg();
+        let got: Vec<_> = cfg.blocks.values().map(|b| (b.start, b.exit)).collect();
+        assert_eq!(exits, &got);
+    }
+
+    // a custom operation falls through, given the table that has it
+    let prog = Program::parse("10,4,5, 99").unwrap();
+    let mut ops = ops::Operations::new();
+    ops.register(ops::Operation {
+        opcode: 10,
+        name: "square",
+        params: 2,
+        action: |_, _| Ok(()),
+    });
+    let exits: Vec<_> = cfg::Cfg::build_with(&prog, &ops)
+        .blocks
+        .values()
+        .map(|b| (b.start, b.instructions.len(), b.exit))
+        .collect();
+    assert_eq!(vec![(0, 2, Exit::Halt)], exits);
+    let exits: Vec<_> = prog.cfg().blocks.values().map(|b| b.exit).collect();
+    assert_eq!(vec![Exit::Invalid(0)], exits);
+
+    // every edge in a real program lands on a block
+    for src in &[
+        include_str!("../../day11/src/input.txt"),
+        include_str!("../../day13/src/input.txt"),
+    ] {
+        let cfg = Program::parse(src).unwrap().cfg();
+        assert!(cfg.blocks.len() > 10);
+        for b in cfg.blocks.values() {
+            for s in b.exit.successors() {
+                assert!(cfg.blocks.contains_key(&s), "{} -> {}", b.start, s);
+            }
+        }
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    // add [0], [0] -> [0] overwrites its own opcode
+    let prog = Program::parse("1,0,0,0,99").unwrap();
+    let mut rt = prog.new_runtime();
+    rt.detect_self_modification(SmcMode::Report);
+    assert_eq!(Ok(RuntimeState::Complete), rt.resume(None));
+    assert_eq!(
+        &[CodeWrite {
+            pc: 0,
+            addr: 0,
+            old: 1,
+            new: 2
+        }],
+        rt.code_writes()
+    );
+
+    let mut rt = prog.new_runtime();
+    rt.detect_self_modification(SmcMode::Error);
+    assert_eq!(
+        Err(IntcodeError::SelfModifyingCode { pc: 0, addr: 0 }),
+        rt.resume(None)
+    );
+    assert_eq!(Ok(1), rt.get(0));
+    // the failed instruction isn't still writing
+    assert_eq!(Ok(()), rt.set(0, 1101));
+
+    // nor is one that failed some other way
+    let mut rt = Program::parse("1002,5,2,5,99,0").unwrap().new_runtime();
+    rt.set(5, Word::MAX).unwrap();
+    rt.detect_self_modification(SmcMode::Report);
+    assert!(matches!(
+        rt.resume(None),
+        Err(IntcodeError::Overflow { pc: 0, .. })
+    ));
+    rt.set(1, 5).unwrap();
+    assert_eq!(0, rt.code_writes().len());
+
+    // an input whose write fails can be sent again, and is still its write
+    let mut rt = Program::parse("3,0,99").unwrap().new_runtime();
+    rt.detect_self_modification(SmcMode::Error);
+    rt.resume(None).unwrap();
+    let smc = Err(IntcodeError::SelfModifyingCode { pc: 0, addr: 0 });
+    assert_eq!(smc, rt.resume(Some(7)));
+    assert_eq!(Ok(()), rt.set(2, 99));
+    assert_eq!(smc, rt.resume(Some(7)));
+
+    // input lands after the instruction has run, but is still its write.
+    // writes from outside the program don't count
+    let mut rt = Program::parse("3,1,99").unwrap().new_runtime();
+    rt.detect_self_modification(SmcMode::Report);
+    rt.set(0, 3).unwrap();
+    rt.resume(None).unwrap();
+    rt.resume(Some(7)).unwrap();
+    rt.set(2, 99).unwrap();
+    assert_eq!(
+        &[CodeWrite {
+            pc: 0,
+            addr: 1,
+            old: 1,
+            new: 7
+        }],
+        rt.code_writes()
+    );
+
+    // day5's diagnostic stores jump targets at address 0, which started out
+    // as its first input instruction
+    let mut rt = Program::parse(include_str!("../../day5/src/input.txt"))
+        .unwrap()
+        .new_runtime();
+    rt.detect_self_modification(SmcMode::Report);
+    rt.run_io(
+        &mut io::IteratorInput::new(vec![5]),
+        &mut io::VecOutput::new(&mut Vec::new()),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![(284, 0, 3, 294), (304, 0, 294, 314)],
+        rt.code_writes()
+            .iter()
+            .map(|w| (w.pc, w.addr, w.old, w.new))
+            .collect::<Vec<_>>()
+    );
+}
+
+// just enough of an executor and channel to drive run_async without pulling
+// in a runtime
+#[cfg(feature = "async")]
+mod local {
+    use super::Word;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::future::{poll_fn, Future};
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct Unpark(std::thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    // poll all the futures until every one is done
+    pub fn run_all<T>(mut futs: Vec<Pin<Box<dyn Future<Output = T> + '_>>>) -> Vec<T> {
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut results: Vec<Option<T>> = futs.iter().map(|_| None).collect();
+        let mut all = poll_fn(|cx| {
+            for (f, r) in futs.iter_mut().zip(results.iter_mut()) {
+                if r.is_none() {
+                    if let Poll::Ready(v) = f.as_mut().poll(cx) {
+                        *r = Some(v);
+                    }
+                }
+            }
+            if results.iter().all(Option::is_some) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        });
+        while Pin::new(&mut all).poll(&mut cx).is_pending() {
+            std::thread::park();
+        }
+        drop(all);
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    #[derive(Default)]
+    struct Inner {
+        queue: VecDeque<Word>,
+        waker: Option<Waker>,
+        closed: bool,
+    }
+
+    // single-threaded unbounded channel; clones share the same queue
+    #[derive(Clone, Default)]
+    pub struct Channel(Rc<RefCell<Inner>>);
+
+    impl Channel {
+        pub fn with(vals: &[Word]) -> Self {
+            let c = Channel::default();
+            c.0.borrow_mut().queue.extend(vals);
+            c
+        }
+        pub fn close(&self) {
+            self.0.borrow_mut().closed = true;
+        }
+        pub fn drain(&self) -> Vec<Word> {
+            self.0.borrow_mut().queue.drain(..).collect()
+        }
+    }
+
+    impl futures_core::Stream for Channel {
+        type Item = Word;
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Word>> {
+            let mut inner = self.0.borrow_mut();
+            match inner.queue.pop_front() {
+                Some(v) => Poll::Ready(Some(v)),
+                None if inner.closed => Poll::Ready(None),
+                None => {
+                    inner.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    impl futures_sink::Sink<Word> for Channel {
+        type Error = std::convert::Infallible;
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn start_send(self: Pin<&mut Self>, item: Word) -> Result<(), Self::Error> {
+            let mut inner = self.0.borrow_mut();
+            inner.queue.push_back(item);
+            if let Some(w) = inner.waker.take() {
+                w.wake();
+            }
+            Ok(())
+        }
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.close();
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn run_async() {
+    use local::{run_all, Channel};
+    use std::future::Future;
+    use std::pin::Pin;
+
+    // single machine, input stream runs dry
+    let prog = Program::parse("3,0,4,0,3,0,4,0,99").unwrap();
+    let (input, output) = (Channel::with(&[7]), Channel::default());
+    input.close();
+    let res = run_all(vec![Box::pin(prog.run_async(input, output.clone()))]);
+    assert_eq!(vec![Err(IntcodeError::InputExhausted)], res);
+    assert_eq!(vec![7], output.drain());
+
+    // day7's feedback loop as five cooperating machines on one thread
+    let prog = Program::parse(
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+    )
+    .unwrap();
+    // the first amplifier also gets the initial signal
+    let wires: Vec<_> = [&[9, 0][..], &[8], &[7], &[6], &[5]]
+        .iter()
+        .map(|vals| Channel::with(vals))
+        .collect();
+    let amps = (0..5)
+        .map(|i| {
+            let f = prog.run_async(wires[i].clone(), wires[(i + 1) % 5].clone());
+            Box::pin(f) as Pin<Box<dyn Future<Output = _>>>
+        })
+        .collect();
+    assert!(run_all(amps).iter().all(Result::is_ok));
+    assert_eq!(vec![139629729], wires[0].drain());
+}
+
+#[test]
+fn ascii_io() {
+    // echo a line, then output 1000
+    let prog = Program::parse("3,100,4,100,1008,100,10,101,1006,101,0,104,1000,99").unwrap();
+    assert_eq!(
+        Ok(("hi there\n".to_string(), vec![1000])),
+        prog.run_ascii("hi there\nnot read")
+    );
+    assert_eq!(
+        Err(IntcodeError::InputExhausted),
+        prog.run_ascii("no newline")
+    );
+    assert_eq!(
+        Err(IntcodeError::NonAscii { ch: 'é' }),
+        prog.run_ascii("café\n")
+    );
+
+    // a reader is only read from once the queued text runs out
+    let mut input = io::AsciiInput::from_reader(std::io::Cursor::new("second\nthird\n"));
+    input.push_str("first ");
+    let mut output = io::AsciiOutput::new();
+    prog.run_io(&mut input, &mut output).unwrap();
+    assert_eq!("first second\n", output.text());
+    assert_eq!(&[1000], output.numbers());
+    assert_eq!(Ok('t' as Word), io::Input::read(&mut input));
+}
+
+#[test]
+fn ascii_session() {
+    use session::Session;
+    use std::io::Cursor;
+
+    // asks "?", echoes a line back, and outputs 1000 and halts after a line
+    // starting with q
+    let prog = Program::assemble(
+        "
+        start:  out #63
+                out #10
+                in [first]
+                out [first]
+                eq [first], #10, [done]     ; empty line, ask again
+                jnz [done], #start
+        rest:   in [c]
+                out [c]
+                eq [c], #10, [done]
+                jz [done], #rest
+                eq [first], #113, [done]
+                jz [done], #start
+                out #1000
+                hlt
+        first:  .data 0
+        c:      .data 0
+        done:   .data 0
+        ",
+    )
+    .unwrap();
+
+    let path = std::env::temp_dir().join(format!("intcode-session-{}.txt", std::process::id()));
+    let mut screen = Vec::new();
+    let mut session = Session::new(&prog).transcript(&path).unwrap();
+    let res = session.run_with(&mut Cursor::new("hello\nquit"), &mut screen);
+    assert_eq!(Ok(vec![1000]), res);
+    assert_eq!(
+        "?\n> hello\n?\n> quit\n1000\n",
+        String::from_utf8(screen).unwrap()
+    );
+    let transcript = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!("?\n> hello\nhello\n?\n> quit\nquit\n1000\n", transcript);
+
+    // non-ASCII lines are refused rather than sent, and running out of
+    // input ends the session
+    let mut screen = Vec::new();
+    let res = Session::new(&prog)
+        .with_prompt("")
+        .run_with(&mut Cursor::new("héllo\n"), &mut screen);
+    assert_eq!(Err(IntcodeError::InputExhausted), res);
+    assert_eq!(
+        "?\nonly ASCII can be sent\n",
+        String::from_utf8(screen).unwrap()
+    );
+}
+
+#[test]
+fn channel_io() {
+    use std::time::Duration;
+
+    // a machine on its own thread; its input blocks until a peer sends
+    let prog = Program::parse("3,0,1002,0,2,0,4,0,99").unwrap();
+    let (mut to_machine, mut input) = io::channel();
+    let (mut output, mut from_machine) = io::channel();
+    let mut rt = prog.new_runtime();
+    let machine = std::thread::spawn(move || rt.run_io(&mut input, &mut output));
+    io::Output::write(&mut to_machine, 21).unwrap();
+    assert_eq!(Ok(42), io::Input::read(&mut from_machine));
+    assert_eq!(Ok(()), machine.join().unwrap());
+
+    // nobody sends: timeout, or disconnect once the sender is gone
+    let (tx, input) = io::channel();
+    let timeout = Duration::from_millis(10);
+    let mut input = input.with_timeout(timeout);
+    assert_eq!(
+        Err(IntcodeError::InputTimeout { timeout }),
+        prog.run_io(&mut input, &mut io::NotImplemented)
+            .map_err(|e| e.root().clone())
+    );
+    drop(tx);
+    assert_eq!(Err(IntcodeError::Disconnected), io::Input::read(&mut input));
+
+    // writing to a machine that's gone
+    let (mut output, input) = io::channel();
+    drop(input);
+    assert_eq!(
+        Err(IntcodeError::Disconnected),
+        io::Output::write(&mut output, 1)
+    );
+}
+
+#[cfg(feature = "crossbeam")]
+#[test]
+fn crossbeam_io() {
+    use io::crossbeam::{CrossbeamInput, CrossbeamOutput};
+    use std::time::Duration;
+
+    let timeout = Duration::from_millis(10);
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let mut output = CrossbeamOutput::new(tx).with_timeout(timeout);
+    let mut input = CrossbeamInput::new(rx).with_timeout(timeout);
+    io::Output::write(&mut output, 1).unwrap();
+    assert_eq!(
+        Err(IntcodeError::OutputTimeout { timeout }),
+        io::Output::write(&mut output, 2)
+    );
+    assert_eq!(Ok(1), io::Input::read(&mut input));
+    assert_eq!(
+        Err(IntcodeError::InputTimeout { timeout }),
+        io::Input::read(&mut input)
+    );
+    drop(output);
+    assert_eq!(Err(IntcodeError::Disconnected), io::Input::read(&mut input));
+}
+
+#[test]
+fn network() {
+    use network::Network;
+    let double = Program::parse("3,0,1002,0,2,0,4,0,99").unwrap();
+
+    // fan-out
+    let mut net = Network::new();
+    let src = net.add_node("src", &Program::parse("104,5,99").unwrap(), vec![]);
+    let a = net.add_node("a", &double, vec![]);
+    let b = net.add_node("b", &double, vec![]);
+    net.connect(src, a);
+    net.connect(src, b);
+    net.run().unwrap();
+    assert_eq!((&[10][..], &[10][..]), (net.outputs(a), net.outputs(b)));
+
+    // a ring where nobody has anything to say
+    let mut net = Network::new();
+    let a = net.add_node("a", &double, vec![]);
+    let b = net.add_node("b", &double, vec![]);
+    let c = net.add_node("c", &double, vec![1]);
+    net.connect(a, b);
+    net.connect(b, a);
+    assert_eq!(
+        Err(IntcodeError::Deadlock {
+            waiting: vec!["a".to_string(), "b".to_string()]
+        }),
+        net.run()
+    );
+    assert!(net.is_halted(c));
+
+    // packets addressed by node id; the receiver polls, getting -1 when idle
+    let receiver = Program::assemble(
+        "
+loop:   in [x]
+        eq [x], #-1, [t]
+        jnz [t], #loop
+        in [y]
+        add [x], [y], [x]
+        out [x]
+        hlt
+x:      .data 0
+y:      .data 0
+t:      .data 0
+        ",
+    )
+    .unwrap();
+    let sender = Program::parse("104,0,104,7,104,8,104,9,104,1,104,2,99").unwrap();
+    let mut net = Network::new();
+    let rx = net.add_node("rx", &receiver, vec![]);
+    let tx = net.add_node("tx", &sender, vec![]);
+    net.set_idle_input(rx, Some(-1));
+    net.route_packets(tx, 3);
+    assert_eq!(Ok(true), net.run_until(|n| !n.unrouted().is_empty()));
+    assert_eq!(&[(tx, vec![9, 1, 2])], net.unrouted());
+    net.run().unwrap();
+    assert_eq!(&[15], net.outputs(rx));
+}
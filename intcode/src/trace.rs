@@ -0,0 +1,183 @@
+use super::ops::Instruction;
+use super::{Int, IntcodeError, Word};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Something happened inside a Runtime. Sinks get these along with the
+// runtime's id, via Runtime::set_tracer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraceEvent<W = Word> {
+    // about to execute the instruction at pc
+    Instruction {
+        pc: Word,
+        instruction: Instruction<W>,
+    },
+    Read {
+        addr: Word,
+        val: W,
+    },
+    Write {
+        addr: Word,
+        val: W,
+    },
+    RelativeBase {
+        delta: Word,
+        rb: Word,
+    },
+    // an input value arrived and is being stored at addr
+    Input {
+        addr: Word,
+        val: W,
+    },
+    Output {
+        val: W,
+    },
+    Halt,
+}
+
+pub trait TraceSink<W = Word> {
+    fn event(&mut self, id: &str, event: &TraceEvent<W>);
+}
+
+// Human readable, one line per event, e.g.
+//
+//   0004: multiply [4], #3, [4]
+//         read [4] -> 33
+//         write [4] <- 99
+pub struct TextSink<O: Write> {
+    out: O,
+}
+
+impl TextSink<std::io::Stdout> {
+    pub fn stdout() -> Self {
+        TextSink::new(std::io::stdout())
+    }
+}
+
+impl<O: Write> TextSink<O> {
+    pub fn new(out: O) -> Self {
+        TextSink { out }
+    }
+    pub fn get_ref(&self) -> &O {
+        &self.out
+    }
+}
+
+impl<W: Int, O: Write> TraceSink<W> for TextSink<O> {
+    fn event(&mut self, id: &str, event: &TraceEvent<W>) {
+        let prefix = if id.is_empty() {
+            String::new()
+        } else {
+            format!("[{}] ", id)
+        };
+        let line = match event {
+            TraceEvent::Instruction { pc, instruction } => format!("{:04}: {}", pc, instruction),
+            TraceEvent::Read { addr, val } => format!("      read [{}] -> {}", addr, val),
+            TraceEvent::Write { addr, val } => format!("      write [{}] <- {}", addr, val),
+            TraceEvent::RelativeBase { delta, rb } => format!("      rb += {} -> {}", delta, rb),
+            TraceEvent::Input { addr, val } => format!("      input [{}] <- {}", addr, val),
+            TraceEvent::Output { val } => format!("      output {}", val),
+            TraceEvent::Halt => "      halt".to_string(),
+        };
+        // tracing is best effort, a broken pipe shouldn't stop the program
+        let _ = writeln!(self.out, "{}{}", prefix, line);
+    }
+}
+
+// One JSON object per line, e.g.
+//
+//   {"id":"0","event":"write","addr":4,"val":99}
+pub struct JsonLinesSink<O: Write> {
+    out: O,
+}
+
+impl JsonLinesSink<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, IntcodeError> {
+        let file = File::create(path).map_err(|e| IntcodeError::Io(e.to_string()))?;
+        Ok(JsonLinesSink::new(BufWriter::new(file)))
+    }
+}
+
+impl<O: Write> JsonLinesSink<O> {
+    pub fn new(out: O) -> Self {
+        JsonLinesSink { out }
+    }
+    pub fn get_ref(&self) -> &O {
+        &self.out
+    }
+}
+
+impl<W: Int, O: Write> TraceSink<W> for JsonLinesSink<O> {
+    fn event(&mut self, id: &str, event: &TraceEvent<W>) {
+        let fields = match event {
+            TraceEvent::Instruction { pc, instruction } => format!(
+                r#""event":"instruction","pc":{},"op":"{}","text":"{}""#,
+                pc, instruction.operation.name, instruction
+            ),
+            TraceEvent::Read { addr, val } => {
+                format!(r#""event":"read","addr":{},"val":{}"#, addr, val)
+            }
+            TraceEvent::Write { addr, val } => {
+                format!(r#""event":"write","addr":{},"val":{}"#, addr, val)
+            }
+            TraceEvent::RelativeBase { delta, rb } => {
+                format!(r#""event":"rb","delta":{},"rb":{}"#, delta, rb)
+            }
+            TraceEvent::Input { addr, val } => {
+                format!(r#""event":"input","addr":{},"val":{}"#, addr, val)
+            }
+            TraceEvent::Output { val } => format!(r#""event":"output","val":{}"#, val),
+            TraceEvent::Halt => r#""event":"halt""#.to_string(),
+        };
+        let _ = writeln!(self.out, r#"{{"id":"{}",{}}}"#, escape(id), fields);
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Keeps the last `capacity` events in memory, mostly for tests
+pub struct RingBuffer<W = Word> {
+    capacity: usize,
+    events: VecDeque<(String, TraceEvent<W>)>,
+}
+
+impl<W: Int> RingBuffer<W> {
+    pub fn new(capacity: usize) -> Self {
+        RingBuffer {
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        }
+    }
+    // (runtime id, event), oldest first
+    pub fn events(&self) -> impl Iterator<Item = &(String, TraceEvent<W>)> {
+        self.events.iter()
+    }
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl<W: Int> TraceSink<W> for RingBuffer<W> {
+    fn event(&mut self, id: &str, event: &TraceEvent<W>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back((id.to_string(), event.clone()));
+    }
+}
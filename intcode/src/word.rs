@@ -0,0 +1,92 @@
+use super::ops::Arithmetic;
+use super::Word;
+use std::fmt::{Debug, Display};
+use std::num::ParseIntError;
+
+// A type a machine can hold in memory and compute with. Word (i64) is the
+// default, and the fastest; i128, or BigInt with the bigint feature, are for
+// programs whose values don't fit in 64 bits. Addresses, the pc and the
+// relative base are Words whatever the value type.
+pub trait Int: Clone + PartialEq + PartialOrd + Debug + Display + Send + Sync + 'static {
+    fn from_word(w: Word) -> Self;
+    // None if it doesn't fit
+    fn to_word(&self) -> Option<Word>;
+    // self % 10^n, with self's sign: enough of an instruction word to decode it
+    fn low_digits(&self, n: u32) -> Word;
+    fn parse(s: &str) -> Result<Self, ParseIntError>;
+    // None on overflow, which only Arithmetic::Checked reports
+    fn add(&self, rhs: &Self, arithmetic: Arithmetic) -> Option<Self>;
+    fn mul(&self, rhs: &Self, arithmetic: Arithmetic) -> Option<Self>;
+}
+
+macro_rules! primitive_int {
+    ($t:ty) => {
+        impl Int for $t {
+            #[inline]
+            fn from_word(w: Word) -> Self {
+                w as $t
+            }
+            #[inline]
+            fn to_word(&self) -> Option<Word> {
+                use std::convert::TryFrom;
+                Word::try_from(*self).ok()
+            }
+            #[inline]
+            fn low_digits(&self, n: u32) -> Word {
+                (*self % (10 as $t).pow(n)) as Word
+            }
+            #[inline]
+            fn parse(s: &str) -> Result<Self, ParseIntError> {
+                s.parse()
+            }
+            #[inline]
+            fn add(&self, rhs: &Self, arithmetic: Arithmetic) -> Option<Self> {
+                match arithmetic {
+                    Arithmetic::Checked => self.checked_add(*rhs),
+                    Arithmetic::Wrapping => Some(self.wrapping_add(*rhs)),
+                    Arithmetic::Saturating => Some(self.saturating_add(*rhs)),
+                }
+            }
+            #[inline]
+            fn mul(&self, rhs: &Self, arithmetic: Arithmetic) -> Option<Self> {
+                match arithmetic {
+                    Arithmetic::Checked => self.checked_mul(*rhs),
+                    Arithmetic::Wrapping => Some(self.wrapping_mul(*rhs)),
+                    Arithmetic::Saturating => Some(self.saturating_mul(*rhs)),
+                }
+            }
+        }
+    };
+}
+
+primitive_int!(i64);
+primitive_int!(i128);
+
+// never overflows, so the arithmetic setting makes no difference
+#[cfg(feature = "bigint")]
+impl Int for num_bigint::BigInt {
+    fn from_word(w: Word) -> Self {
+        w.into()
+    }
+    fn to_word(&self) -> Option<Word> {
+        use std::convert::TryFrom;
+        Word::try_from(self).ok()
+    }
+    fn low_digits(&self, n: u32) -> Word {
+        let digits = self % num_bigint::BigInt::from(10i64.pow(n));
+        digits.to_word().unwrap()
+    }
+    fn parse(s: &str) -> Result<Self, ParseIntError> {
+        // BigInt has its own error type; anything it rejects, Word does too
+        match s.parse::<Word>() {
+            Ok(w) => Ok(w.into()),
+            Err(e) => s.parse().map_err(|_| e),
+        }
+    }
+    fn add(&self, rhs: &Self, _: Arithmetic) -> Option<Self> {
+        Some(self + rhs)
+    }
+    fn mul(&self, rhs: &Self, _: Arithmetic) -> Option<Self> {
+        Some(self * rhs)
+    }
+}